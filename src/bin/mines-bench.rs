@@ -0,0 +1,210 @@
+//! Plays seeded games with the built-in solver and reports how it fares, as a
+//! baseline for changes to board generation or the solver.
+
+use std::{
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use mines::{deduce, probabilities, Config, Difficulty, Game, Status};
+
+const USAGE: &str = "usage: mines-bench [--games <number>] [--seed <first seed>]";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Options {
+    games: u64,
+    seed: u64,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        games: 1000,
+        seed: 0,
+    };
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+
+        let slot = match flag.as_str() {
+            "--games" => &mut options.games,
+            "--seed" => &mut options.seed,
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument: {flag}\n{USAGE}")),
+        };
+        let value = value
+            .or_else(|| args.next())
+            .ok_or(format!("{flag} needs a value"))?;
+        *slot = value
+            .parse()
+            .map_err(|_| format!("invalid number for {flag}: {value}"))?;
+    }
+
+    if options.games == 0 {
+        return Err("--games must be at least 1".to_string());
+    }
+    if options.seed.checked_add(options.games - 1).is_none() {
+        return Err(format!(
+            "--seed {} leaves no room for {} games",
+            options.seed, options.games
+        ));
+    }
+    Ok(options)
+}
+
+/// How one game went.
+struct Outcome {
+    won: bool,
+    /// Reveals the solver couldn't prove safe, including the first click.
+    guesses: u32,
+}
+
+/// Plays a game to the end: the first click goes in the middle, then every
+/// provable move is made, and when there are none the hidden cell least
+/// likely to be a mine is revealed.
+fn play(game: &mut Game) -> Outcome {
+    let (rows, cols) = game.config().size;
+    let num_bombs = game.config().num_bombs;
+    let mut guesses = 1;
+    game.reveal((rows / 2, cols / 2));
+
+    while !game.is_over() {
+        let found = deduce(game.board(), num_bombs);
+        if !found.is_empty() {
+            for (i, n) in found.mines {
+                for _ in 0..n {
+                    game.toggle_flag(i);
+                }
+            }
+            for i in found.safe {
+                game.reveal(i);
+            }
+            continue;
+        }
+
+        let mut hidden = game
+            .board()
+            .indices()
+            .filter(|&i| game.board()[i].state.is_covered());
+        let odds = probabilities(game.board(), num_bombs);
+        let guess = match odds {
+            Ok(odds) => hidden.min_by(|&a, &b| {
+                let (a, b) = (odds.get(a).unwrap_or(1.0), odds.get(b).unwrap_or(1.0));
+                a.total_cmp(&b)
+            }),
+            Err(_) => hidden.next(),
+        };
+        let Some(guess) = guess else {
+            break;
+        };
+        guesses += 1;
+        game.reveal(guess);
+    }
+
+    Outcome {
+        won: game.status() == Status::Won,
+        guesses,
+    }
+}
+
+struct Report {
+    games: u64,
+    wins: u64,
+    guesses: u64,
+    time: Duration,
+}
+
+fn bench(difficulty: Difficulty, options: &Options) -> Report {
+    let mut report = Report {
+        games: options.games,
+        wins: 0,
+        guesses: 0,
+        time: Duration::ZERO,
+    };
+
+    let start = Instant::now();
+    for seed in options.seed..=options.seed + (options.games - 1) {
+        let mut game = Game::with_seed(Config::from(&difficulty), seed);
+        let outcome = play(&mut game);
+        report.wins += outcome.won as u64;
+        report.guesses += outcome.guesses as u64;
+    }
+    report.time = start.elapsed();
+    report
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!(
+        "{} games per difficulty, seeds {} to {}",
+        options.games,
+        options.seed,
+        options.seed + (options.games - 1)
+    );
+    println!(
+        "{:<14} {:>8} {:>10} {:>12}",
+        "difficulty", "win rate", "guesses", "games/s"
+    );
+    for d in Difficulty::ALL {
+        let report = bench(d, &options);
+        println!(
+            "{:<14} {:>7.1}% {:>10.2} {:>12.0}",
+            d.name(),
+            100.0 * report.wins as f64 / report.games as f64,
+            report.guesses as f64 / report.games as f64,
+            report.games as f64 / report.time.as_secs_f64()
+        );
+    }
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse(&[]).unwrap(),
+            Options {
+                games: 1000,
+                seed: 0
+            }
+        );
+        assert_eq!(
+            parse(&["--games", "50", "--seed=9"]).unwrap(),
+            Options { games: 50, seed: 9 }
+        );
+        assert!(parse(&["--games", "0"]).is_err());
+        assert!(parse(&["--seed", "18446744073709551615", "--games", "2"]).is_err());
+        assert!(parse(&["--seed", "18446744073709551615", "--games", "1"]).is_ok());
+        assert!(parse(&["--games"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_play_to_the_end() {
+        for seed in 0..20 {
+            let mut game = Game::with_seed(Config::from(&Difficulty::Beginner), seed);
+            let outcome = play(&mut game);
+            assert!(game.is_over());
+            assert_eq!(outcome.won, game.status() == Status::Won);
+            assert!(outcome.guesses >= 1);
+        }
+    }
+}
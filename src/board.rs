@@ -0,0 +1,333 @@
+use std::ops::{Index, IndexMut};
+
+use rand::Rng;
+
+use crate::topology::{Pos, Topology};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellContents {
+    /// How many mines the cell holds, at least one.
+    Bomb(u32),
+    /// How many mines the neighbors hold between them.
+    Hint(u32),
+}
+
+impl CellContents {
+    pub fn is_bomb(&self) -> bool {
+        matches!(self, CellContents::Bomb(_))
+    }
+
+    /// How many mines the cell holds.
+    pub fn mines(&self) -> u32 {
+        match self {
+            CellContents::Bomb(n) => *n,
+            CellContents::Hint(_) => 0,
+        }
+    }
+}
+
+impl Default for CellContents {
+    fn default() -> Self {
+        Self::Hint(0)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum CellState {
+    #[default]
+    Hidden,
+    Revealed,
+    /// Flagged as holding this many mines, at least one.
+    Flagged(u32),
+    /// Marked with a question mark: unsure, but not flagged.
+    Questioned,
+}
+
+impl CellState {
+    /// Whether the cell is still closed and unflagged, i.e. hidden with or
+    /// without a question mark.
+    pub fn is_covered(&self) -> bool {
+        matches!(self, CellState::Hidden | CellState::Questioned)
+    }
+
+    /// How many mines the player has flagged here.
+    pub fn flags(&self) -> u32 {
+        match self {
+            CellState::Flagged(n) => *n,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Cell {
+    pub contents: CellContents,
+    pub state: CellState,
+}
+
+/// The cells of a game, indexed by [`Pos`]. The board knows about
+/// adjacency and hints but not about whose turn it is or whether the game is
+/// over; that is [`crate::Game`]'s job.
+#[derive(Debug, Clone)]
+pub struct Board {
+    topology: Topology,
+    cells: Vec<Cell>,
+    /// How many mines a cell can hold.
+    max_mines: u32,
+}
+
+impl Board {
+    /// A rectangular board of `(rows, columns)`.
+    pub fn new(size: (usize, usize)) -> Board {
+        Board::with_topology(Topology::rect(size))
+    }
+
+    pub fn with_topology(topology: Topology) -> Board {
+        Board::with_max_mines(topology, 1)
+    }
+
+    /// A board whose cells can each hold up to `max_mines` mines.
+    pub fn with_max_mines(topology: Topology, max_mines: u32) -> Board {
+        let cells = vec![Cell::default(); topology.len()];
+
+        Board {
+            topology,
+            cells,
+            max_mines,
+        }
+    }
+
+    /// `(rows, columns)` of each layer.
+    pub fn size(&self) -> (usize, usize) {
+        self.topology.size()
+    }
+
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    pub fn max_mines(&self) -> u32 {
+        self.max_mines
+    }
+
+    /// Every cell's index, in the same order as [`Board::cells`].
+    pub fn indices(&self) -> impl Iterator<Item = Pos> {
+        self.topology.indices()
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = &Cell> {
+        self.cells.iter()
+    }
+
+    pub fn num_cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Reveals the cell at `index`, flooding outwards through empty cells and
+    /// stopping at the numbered cells that border them. Flags are left alone,
+    /// question marks are not.
+    pub fn reveal(&mut self, index: impl Into<Pos>) {
+        let mut stack = vec![index.into()];
+        while let Some(current) = stack.pop() {
+            let cell = &mut self[current];
+            if !cell.state.is_covered() {
+                continue;
+            }
+            cell.state = CellState::Revealed;
+
+            if let CellContents::Hint(0) = cell.contents {
+                stack.append(&mut self.neighbors(current));
+            }
+        }
+    }
+
+    /// Puts a mine at `index`, on top of any already there, and bumps the
+    /// hints around it. Returns `false` if the cell was already full.
+    pub fn place_bomb(&mut self, index: impl Into<Pos>) -> bool {
+        let index = index.into();
+        let max = self.max_mines;
+        let cell = &mut self[index];
+        cell.contents = match cell.contents {
+            CellContents::Bomb(n) if n >= max => return false,
+            CellContents::Bomb(n) => CellContents::Bomb(n + 1),
+            CellContents::Hint(_) => CellContents::Bomb(1),
+        };
+
+        for neighbor in self.neighbors(index) {
+            let neighbor_cell = &mut self[neighbor];
+            if let CellContents::Hint(n) = neighbor_cell.contents {
+                neighbor_cell.contents = CellContents::Hint(n + 1);
+            }
+        }
+        true
+    }
+
+    /// Whether every cell that isn't a bomb has been revealed.
+    pub fn is_cleared(&self) -> bool {
+        self.cells
+            .iter()
+            .all(|cell| cell.contents.is_bomb() || cell.state == CellState::Revealed)
+    }
+
+    /// Flags every bomb with its number of mines, used to show the finished
+    /// board after a win.
+    pub fn flag_bombs(&mut self) {
+        for cell in &mut self.cells {
+            if let CellContents::Bomb(n) = cell.contents {
+                cell.state = CellState::Flagged(n);
+            }
+        }
+    }
+
+    /// The cells touching `index`.
+    pub fn neighbors(&self, index: impl Into<Pos>) -> Vec<Pos> {
+        self.topology.neighbors(index)
+    }
+}
+
+impl<I: Into<Pos>> Index<I> for Board {
+    type Output = Cell;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.cells[self.topology.offset(index)]
+    }
+}
+
+impl<I: Into<Pos>> IndexMut<I> for Board {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        &mut self.cells[self.topology.offset(index)]
+    }
+}
+
+/// Places `num_bombs` mines one at a time on cells picked uniformly at
+/// random, avoiding the cells in `safe` and any that are already full.
+pub fn place_bombs_rnd<R: Rng>(rng: R, board: &mut Board, num_bombs: u32, safe: &[Pos]) {
+    let mut rng = rng;
+
+    let (r, c) = board.size();
+    let layers = board.topology().layers();
+    let mut bombs_placed = 0;
+    while bombs_placed < num_bombs {
+        // Layers are picked along with the row, which keeps the same boards
+        // for the same seeds where there is only one.
+        let row = rng.gen_range(0..r * layers);
+        let index = Pos::new(row / r, row % r, rng.gen_range(0..c));
+        if !safe.contains(&index) && board.place_bomb(index) {
+            bombs_placed += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::topology::Shape;
+
+    #[test]
+    fn test_neighbors() {
+        let board = Board::new((5, 5));
+
+        let mut neighbors = board.neighbors((2, 2));
+        neighbors.sort();
+        assert_eq!(
+            neighbors,
+            vec![
+                (1, 1).into(),
+                (1, 2).into(),
+                (1, 3).into(),
+                (2, 1).into(),
+                (2, 3).into(),
+                (3, 1).into(),
+                (3, 2).into(),
+                (3, 3).into()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_neighbors_on_right_edge() {
+        let board = Board::new((3, 5));
+
+        let mut neighbors = board.neighbors((1, 4));
+        neighbors.sort();
+        assert_eq!(
+            neighbors,
+            vec![
+                (0, 3).into(),
+                (0, 4).into(),
+                (1, 3).into(),
+                (2, 3).into(),
+                (2, 4).into()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reveal_floods_through_question_marks() {
+        let mut board = Board::new((1, 5));
+        board.place_bomb((0, 4));
+        board[(0, 1)].state = CellState::Questioned;
+        board[(0, 2)].state = CellState::Flagged(1);
+
+        board.reveal((0, 0));
+        assert_eq!(board[(0, 1)].state, CellState::Revealed);
+        assert_eq!(board[(0, 2)].state, CellState::Flagged(1));
+    }
+
+    #[test]
+    fn test_reveal_floods_to_win() {
+        let mut board = Board::new((5, 5));
+        board.place_bomb((4, 4));
+        assert_eq!(board[(3, 3)].contents, CellContents::Hint(1));
+
+        board.reveal((0, 0));
+        assert_eq!(board[(3, 3)].state, CellState::Revealed);
+        assert_eq!(board[(4, 4)].state, CellState::Hidden);
+        assert!(board.is_cleared());
+
+        board.flag_bombs();
+        assert_eq!(board[(4, 4)].state, CellState::Flagged(1));
+    }
+
+    #[test]
+    fn test_torus_hints_and_reveal_wrap() {
+        let mut board = Board::with_topology(Topology::torus((4, 5)));
+        board.place_bomb((0, 2));
+        assert_eq!(board[(3, 1)].contents, CellContents::Hint(1));
+        assert_eq!(board[(3, 4)].contents, CellContents::Hint(0));
+
+        // Opening the far column floods back round to the near one.
+        board.reveal((2, 4));
+        assert_eq!(board[(2, 0)].state, CellState::Revealed);
+        assert_eq!(board[(0, 2)].state, CellState::Hidden);
+        assert!(board.is_cleared());
+    }
+
+    #[test]
+    fn test_hex_hints() {
+        let mut board = Board::with_topology(Topology::new((3, 3), Shape::Hex, false));
+        board.place_bomb((1, 1));
+        for i in [(0, 1), (0, 2), (1, 0), (1, 2), (2, 1), (2, 2)] {
+            assert_eq!(board[i].contents, CellContents::Hint(1), "{i:?}");
+        }
+        assert_eq!(board[(0, 0)].contents, CellContents::Hint(0));
+        assert_eq!(board[(2, 0)].contents, CellContents::Hint(0));
+    }
+
+    #[test]
+    fn test_stacked_mines() {
+        let mut board = Board::with_max_mines(Topology::rect((3, 3)), 3);
+        for _ in 0..3 {
+            assert!(board.place_bomb((0, 0)));
+        }
+        assert!(!board.place_bomb((0, 0)));
+        board.place_bomb((2, 2));
+        assert_eq!(board[(0, 0)].contents, CellContents::Bomb(3));
+        assert_eq!(board[(1, 1)].contents, CellContents::Hint(4));
+        assert_eq!(board[(0, 1)].contents, CellContents::Hint(3));
+
+        board.flag_bombs();
+        assert_eq!(board[(0, 0)].state, CellState::Flagged(3));
+        assert_eq!(board[(2, 2)].state.flags(), 1);
+    }
+}
@@ -0,0 +1,237 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    game::{Config, SafeStart},
+    topology::Shape,
+};
+
+/// A compact, shareable description of a board: its size, with the number of
+/// layers after a second `x` if there are several, mine count, first click
+/// rule, an `n` for no-guess boards, `m2` or `m3` where cells hold that many
+/// mines, an `h` for hexagons, a `t` for tori, and seed, e.g.
+/// `9x9-10a-2kx7f0`, `16x30-99anm3ht-1b` or `5x5x4-20a-1b`. Loading the same
+/// code gives the same mine layout, apart from the cells kept clear around
+/// the first click.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameCode {
+    pub config: Config,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCodeError;
+
+impl fmt::Display for ParseCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "not a valid game code (expected something like 9x9-10a-2kx7f0)"
+        )
+    }
+}
+
+impl fmt::Display for GameCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (r, c) = self.config.size;
+        let layers = match self.config.layers {
+            1 => String::new(),
+            n => format!("x{n}"),
+        };
+        let safe = match self.config.safe_start {
+            SafeStart::Area => 'a',
+            SafeStart::Cell => 'c',
+        };
+        let no_guess = if self.config.no_guess { "n" } else { "" };
+        let stacked = match self.config.max_mines {
+            1 => String::new(),
+            n => format!("m{n}"),
+        };
+        let hex = if self.config.shape == Shape::Hex {
+            "h"
+        } else {
+            ""
+        };
+        let torus = if self.config.torus { "t" } else { "" };
+        write!(
+            f,
+            "{r}x{c}{layers}-{}{safe}{no_guess}{stacked}{hex}{torus}-{}",
+            self.config.num_bombs,
+            to_base36(self.seed)
+        )
+    }
+}
+
+impl FromStr for GameCode {
+    type Err = ParseCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let mut parts = s.split('-');
+        let (Some(size), Some(mines), Some(seed), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ParseCodeError);
+        };
+
+        let mut size = size.split('x');
+        let (Some(r), Some(c), layers, None) = (size.next(), size.next(), size.next(), size.next())
+        else {
+            return Err(ParseCodeError);
+        };
+        let (num_bombs, flags) = mines.split_at(
+            mines
+                .find(|ch: char| !ch.is_ascii_digit())
+                .ok_or(ParseCodeError)?,
+        );
+        let (safe_start, flags) = if let Some(flags) = flags.strip_prefix('a') {
+            (SafeStart::Area, flags)
+        } else if let Some(flags) = flags.strip_prefix('c') {
+            (SafeStart::Cell, flags)
+        } else {
+            return Err(ParseCodeError);
+        };
+        // The rest are optional, but must come in this order.
+        let mut flags = flags;
+        let no_guess = take(&mut flags, 'n');
+        let max_mines = if take(&mut flags, 'm') {
+            let digits = flags.find(|ch: char| !ch.is_ascii_digit());
+            let (n, rest) = flags.split_at(digits.unwrap_or(flags.len()));
+            flags = rest;
+            n.parse().map_err(|_| ParseCodeError)?
+        } else {
+            1
+        };
+        let hex = take(&mut flags, 'h');
+        let torus = take(&mut flags, 't');
+        if !flags.is_empty() {
+            return Err(ParseCodeError);
+        }
+
+        let number = |s: &str| s.parse().map_err(|_| ParseCodeError);
+        Ok(GameCode {
+            config: Config {
+                size: (number(r)?, number(c)?),
+                num_bombs: num_bombs.parse().map_err(|_| ParseCodeError)?,
+                max_mines,
+                safe_start,
+                no_guess,
+                shape: if hex { Shape::Hex } else { Shape::Square },
+                torus,
+                layers: layers.map_or(Ok(1), number)?,
+            },
+            seed: u64::from_str_radix(seed, 36).map_err(|_| ParseCodeError)?,
+        })
+    }
+}
+
+/// Strips `letter` off the front of `flags`, returning whether it was there.
+fn take(flags: &mut &str, letter: char) -> bool {
+    match flags.strip_prefix(letter) {
+        Some(rest) => {
+            *flags = rest;
+            true
+        }
+        None => false,
+    }
+}
+
+fn to_base36(mut n: u64) -> String {
+    const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+    let mut digits = Vec::new();
+    loop {
+        digits.push(DIGITS[(n % 36) as usize]);
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Difficulty;
+
+    #[test]
+    fn test_round_trip() {
+        for seed in [0, 35, 36, 12345678, u64::MAX] {
+            let code = GameCode {
+                config: Config::from(&Difficulty::Expert),
+                seed,
+            };
+            assert_eq!(code.to_string().parse(), Ok(code));
+        }
+
+        let code: GameCode = " 9X9-10C-ZZ ".parse().unwrap();
+        assert_eq!(code.config.size, (9, 9));
+        assert_eq!(code.config.num_bombs, 10);
+        assert_eq!(code.config.safe_start, SafeStart::Cell);
+        assert_eq!(code.seed, 36 * 36 - 1);
+        assert!(!code.config.no_guess);
+        assert_eq!(code.to_string(), "9x9-10c-zz");
+
+        let code: GameCode = "16x30-99an-1b".parse().unwrap();
+        assert!(code.config.no_guess);
+        assert_eq!(code.config.safe_start, SafeStart::Area);
+        assert_eq!(code.to_string(), "16x30-99an-1b");
+        assert!(!code.config.torus);
+
+        let code: GameCode = "9x9-10ct-1b".parse().unwrap();
+        assert!(code.config.torus);
+        assert!(!code.config.no_guess);
+        assert_eq!(code.to_string(), "9x9-10ct-1b");
+        assert_eq!(
+            "16x30-99ant-1b".parse::<GameCode>().unwrap().to_string(),
+            "16x30-99ant-1b"
+        );
+
+        let code: GameCode = "16x30-99ah-1b".parse().unwrap();
+        assert_eq!(code.config.shape, Shape::Hex);
+        assert!(!code.config.torus);
+        assert_eq!(code.to_string(), "16x30-99ah-1b");
+        let code: GameCode = "16x30-99anht-1b".parse().unwrap();
+        assert_eq!(code.to_string(), "16x30-99anht-1b");
+
+        let code: GameCode = "16x30-200anm3ht-1b".parse().unwrap();
+        assert_eq!(code.config.max_mines, 3);
+        assert_eq!(code.config.shape, Shape::Hex);
+        assert_eq!(code.to_string(), "16x30-200anm3ht-1b");
+        let code: GameCode = "9x9-20cm2-1b".parse().unwrap();
+        assert_eq!(code.config.max_mines, 2);
+        assert_eq!(code.to_string(), "9x9-20cm2-1b");
+
+        let code: GameCode = "5x5x4-20a-1b".parse().unwrap();
+        assert_eq!(code.config.size, (5, 5));
+        assert_eq!(code.config.layers, 4);
+        assert_eq!(code.to_string(), "5x5x4-20a-1b");
+        let code: GameCode = "5x5x1-20a-1b".parse().unwrap();
+        assert_eq!(code.to_string(), "5x5-20a-1b");
+    }
+
+    #[test]
+    fn test_invalid_codes() {
+        for s in [
+            "",
+            "9x9-10a",
+            "9x9-10a-zz-1",
+            "9-10a-zz",
+            "9x9x-10a-zz",
+            "9x9x2x2-10a-zz",
+            "9x9-10-zz",
+            "9x9-10a-!",
+            "9x9-10na-zz",
+            "9x9-10atn-zz",
+            "9x9-10ath-zz",
+            "9x9-10ahh-zz",
+            "9x9-10am-zz",
+            "9x9-10ahm2-zz",
+            "9x9-10é-zz",
+            "9x9-a-zz",
+        ] {
+            assert_eq!(s.parse::<GameCode>(), Err(ParseCodeError), "{s}");
+        }
+    }
+}
@@ -1,333 +1,1060 @@
-use std::ops::{Index, IndexMut};
-
-use cursive::{
-    align::HAlign,
-    direction::Direction,
-    event::{Event, EventResult, MouseButton, MouseEvent},
-    views::{Button, Dialog, LinearLayout, PaddedView, Panel, SelectView},
-    Cursive, Vec2, View, XY,
+use std::{
+    fmt,
+    time::{Duration, Instant},
 };
-use rand::Rng;
 
-#[derive(Debug)]
-enum Difficulty {
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    board::{place_bombs_rnd, Board, CellContents, CellState},
+    code::GameCode,
+    solver::{self, is_solvable, Hint},
+    topology::{Pos, Shape, Topology},
+};
+
+/// The most mines a single cell can be set to hold.
+pub const MAX_MINES_PER_CELL: u32 = 3;
+
+/// The most layers a board can have.
+pub const MAX_LAYERS: usize = 9;
+
+/// How long a no-guess game may spend looking for a solvable board before it
+/// settles for a random one.
+const NO_GUESS_BUDGET: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
     Beginner,
     Intermediate,
     Expert,
 }
 
-pub fn start_menu(s: &mut Cursive) {
-    s.pop_layer();
-
-    let select = SelectView::new()
-        .h_align(HAlign::Left)
-        .item("Beginner", Difficulty::Beginner)
-        .item("Intermediate", Difficulty::Intermediate)
-        .item("Expert", Difficulty::Expert)
-        .on_submit(new_game);
-
-    s.add_layer(
-        Dialog::around(
-            LinearLayout::vertical()
-                .child(PaddedView::lrtb(
-                    0,
-                    0,
-                    1,
-                    0,
-                    Panel::new(select).title("New Game"),
-                ))
-                .child(Button::new("Top Scores", top_scores))
-                .child(Button::new("Quit", |s| s.quit())),
-        )
-        .title("Mines!"),
-    );
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [
+        Difficulty::Beginner,
+        Difficulty::Intermediate,
+        Difficulty::Expert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Intermediate => "Intermediate",
+            Difficulty::Expert => "Expert",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Difficulty> {
+        Difficulty::ALL.into_iter().find(|d| d.name() == name)
+    }
 }
 
-fn top_scores(s: &mut Cursive) {
-    s.pop_layer();
+/// How much of the board the first reveal is guaranteed to leave clear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafeStart {
+    /// Only the clicked cell.
+    Cell,
+    /// The clicked cell and all of its neighbors, so the first click always
+    /// opens an area.
+    Area,
+}
 
-    s.add_layer(
-        Dialog::text("Scores...")
-            .title("High Scores")
-            .button("Back", start_menu),
-    )
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub size: (usize, usize),
+    /// Mines in total, counting each one in a cell that holds several.
+    pub num_bombs: u32,
+    /// How many mines a cell can hold, from 1 for the usual game up to
+    /// [`MAX_MINES_PER_CELL`].
+    pub max_mines: u32,
+    pub safe_start: SafeStart,
+    /// Only deal boards that can be cleared from the first click by logic
+    /// alone, see [`crate::is_solvable`].
+    pub no_guess: bool,
+    pub shape: Shape,
+    /// Wrap the edges around, see [`Topology::torus`].
+    pub torus: bool,
+    /// How many boards of `size` are stacked up, at most [`MAX_LAYERS`], see
+    /// [`Topology::with_layers`].
+    pub layers: usize,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-enum CellContents {
-    Bomb,
-    Hint(u32),
+pub enum ConfigError {
+    Empty,
+    TooBig,
+    NoMines,
+    TooManyMines { max: usize },
+    MinesPerCell,
+    TooSmallForTorus,
+    OddHexTorus,
+    StackedLayers,
+    TooManyLayers,
 }
 
-impl Default for CellContents {
-    fn default() -> Self {
-        Self::Hint(0)
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Empty => write!(
+                f,
+                "The board needs at least one row, one column and one layer."
+            ),
+            ConfigError::TooBig => write!(f, "The board is too big."),
+            ConfigError::NoMines => write!(f, "There must be at least one mine."),
+            ConfigError::TooManyMines { max } => {
+                write!(f, "This board has room for at most {max} mines.")
+            }
+            ConfigError::MinesPerCell => {
+                write!(f, "A cell can hold 1 to {MAX_MINES_PER_CELL} mines.")
+            }
+            ConfigError::TooSmallForTorus => {
+                write!(f, "A torus needs at least 3 rows and 3 columns.")
+            }
+            ConfigError::OddHexTorus => {
+                write!(f, "A hexagonal torus needs an even number of rows.")
+            }
+            ConfigError::StackedLayers => {
+                write!(f, "A cell of a layered board holds a single mine.")
+            }
+            ConfigError::TooManyLayers => {
+                write!(f, "A board can have at most {MAX_LAYERS} layers.")
+            }
+        }
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
-enum CellState {
-    #[default]
-    Hidden,
-    Revealed,
-    Flagged,
+impl Config {
+    /// Checks that the board can be played: it has cells, at least one mine,
+    /// and room for the first reveal to be safe. A torus must be big enough
+    /// that no cell is its own neighbor, and hexagons only wrap from bottom
+    /// to top when the rows pair up. Layered boards keep to one mine a cell,
+    /// which already makes for hints up to 26.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let (r, c) = self.size;
+        if r == 0 || c == 0 || self.layers == 0 {
+            return Err(ConfigError::Empty);
+        }
+        if self.layers > MAX_LAYERS {
+            return Err(ConfigError::TooManyLayers);
+        }
+        if self.num_bombs == 0 {
+            return Err(ConfigError::NoMines);
+        }
+        if !(1..=MAX_MINES_PER_CELL).contains(&self.max_mines) {
+            return Err(ConfigError::MinesPerCell);
+        }
+        if self.layers > 1 && self.max_mines > 1 {
+            return Err(ConfigError::StackedLayers);
+        }
+        let max = r
+            .checked_mul(c)
+            .and_then(|n| n.checked_mul(self.layers))
+            .and_then(|n| (n - 1).checked_mul(self.max_mines as usize))
+            .ok_or(ConfigError::TooBig)?;
+        if self.num_bombs as usize > max {
+            return Err(ConfigError::TooManyMines { max });
+        }
+        if self.torus && (r < 3 || c < 3) {
+            return Err(ConfigError::TooSmallForTorus);
+        }
+        if self.torus && self.shape == Shape::Hex && r % 2 == 1 {
+            return Err(ConfigError::OddHexTorus);
+        }
+        Ok(())
+    }
+
+    /// A board of `size` with `num_bombs` mines, otherwise set up like the
+    /// presets.
+    pub fn custom(size: (usize, usize), num_bombs: u32) -> Config {
+        Config {
+            size,
+            num_bombs,
+            ..Config::from(&Difficulty::Beginner)
+        }
+    }
+
+    pub fn topology(&self) -> Topology {
+        Topology::new(self.size, self.shape, self.torus).with_layers(self.layers)
+    }
+
+    /// An empty board of this shape and size.
+    pub fn board(&self) -> Board {
+        Board::with_max_mines(self.topology(), self.max_mines)
+    }
 }
 
-#[derive(Debug, Default, Clone)]
-struct Cell {
-    contents: CellContents,
-    state: CellState,
+impl From<&Difficulty> for Config {
+    fn from(value: &Difficulty) -> Self {
+        match value {
+            Difficulty::Beginner => Config {
+                size: (9, 9),
+                num_bombs: 10,
+                max_mines: 1,
+                safe_start: SafeStart::Area,
+                no_guess: false,
+                shape: Shape::Square,
+                torus: false,
+                layers: 1,
+            },
+            Difficulty::Intermediate => Config {
+                size: (16, 16),
+                num_bombs: 40,
+                max_mines: 1,
+                safe_start: SafeStart::Area,
+                no_guess: false,
+                shape: Shape::Square,
+                torus: false,
+                layers: 1,
+            },
+            Difficulty::Expert => Config {
+                size: (16, 30),
+                num_bombs: 99,
+                max_mines: 1,
+                safe_start: SafeStart::Area,
+                no_guess: false,
+                shape: Shape::Square,
+                torus: false,
+                layers: 1,
+            },
+        }
+    }
 }
 
-fn blow_up(s: &mut Cursive) {
-    s.add_layer(Dialog::text("!!!! BOOOM !!!!").button("Try Again", start_menu));
+/// Something the player can do to a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Reveal,
+    Flag,
+    Chord,
 }
 
-struct Grid {
-    size: (usize, usize),
-    cells: Vec<Cell>,
+/// A move that changed the board, and when on the game clock it was made.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub action: Action,
+    pub index: Pos,
+    pub at: Duration,
 }
 
-const NUMBERS: [&str; 9] = [
-    "[0]", "[1]", "[2]", "[3]", "[4]", "[5]", "[6]", "[7]", "[8]",
-];
-
-impl View for Grid {
-    fn take_focus(&mut self, _: Direction) -> Result<EventResult, cursive::view::CannotFocus> {
-        Ok(EventResult::Consumed(None))
-    }
-
-    fn on_event(&mut self, e: Event) -> EventResult {
-        if let Event::Mouse {
-            offset,
-            position,
-            event,
-        } = e
-        {
-            if let Some(XY { x, y }) = position.checked_sub(offset) {
-                let (r, c) = (y, x / 3);
-                if r < self.size.0 && c < self.size.1 {
-                    let cell = &mut self[(r, c)];
-                    if cell.state != CellState::Revealed {
-                        match event {
-                            MouseEvent::Press(MouseButton::Left) => {
-                                if cell.contents == CellContents::Bomb {
-                                    return EventResult::with_cb(|s| blow_up(s));
-                                }
-                                self.reveal((r, c));
-                            }
-                            MouseEvent::Press(MouseButton::Right) => {
-                                if cell.state == CellState::Flagged {
-                                    cell.state = CellState::Hidden;
-                                } else {
-                                    cell.state = CellState::Flagged;
-                                }
-
-                                return EventResult::Consumed(None);
-                            }
-                            _ => (),
-                        };
-                    }
-                }
-            }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Playing,
+    Won,
+    Lost,
+}
+
+/// The parts of a [`Game`] that undo and redo roll back.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    board: Board,
+    status: Status,
+    armed: bool,
+    finished: Option<Duration>,
+    exploded: Option<Pos>,
+    moves: Vec<Move>,
+}
+
+/// A single game: the board plus the rules for playing it. Moves on a
+/// finished game are ignored.
+#[derive(Debug, Clone)]
+pub struct Game {
+    board: Board,
+    config: Config,
+    /// Seeds the bomb placement, so the same seed gives the same board.
+    seed: u64,
+    status: Status,
+    /// Bombs are only placed once the first cell is revealed.
+    armed: bool,
+    /// When the first cell was revealed, which starts the clock.
+    start: Option<Instant>,
+    /// Time played before the game was resumed.
+    previous: Duration,
+    /// The final time, once the game is won or lost.
+    finished: Option<Duration>,
+    /// The bomb that lost the game, which is left revealed.
+    exploded: Option<Pos>,
+    /// Set when a no-guess game ran out of time looking for a solvable board.
+    fell_back: bool,
+    /// How many times the player asked for a hint.
+    hints: u32,
+    /// Every move so far, oldest first, for replays.
+    moves: Vec<Move>,
+    /// Whether right click cycles through question marks as well as flags.
+    question_marks: bool,
+    /// In practice mode: the undos used so far.
+    undos: Option<u32>,
+    /// In practice mode: the state before each move, and the states undone.
+    history: Vec<Snapshot>,
+    future: Vec<Snapshot>,
+}
+
+impl Game {
+    /// A game with a random seed.
+    pub fn new(config: Config) -> Game {
+        Game::with_seed(config, rand::thread_rng().gen())
+    }
+
+    pub fn with_seed(config: Config, seed: u64) -> Game {
+        Game {
+            board: config.board(),
+            config,
+            seed,
+            status: Status::Playing,
+            armed: false,
+            start: None,
+            previous: Duration::ZERO,
+            finished: None,
+            exploded: None,
+            fell_back: false,
+            hints: 0,
+            moves: Vec::new(),
+            question_marks: false,
+            undos: None,
+            history: Vec::new(),
+            future: Vec::new(),
         }
+    }
 
-        EventResult::Ignored
+    /// Picks up a game saved part way through: `board` as it was left, the
+    /// time already on the clock, the hints used so far and the moves that
+    /// led there. `undos` is `Some` for practice games, counting the undos
+    /// used; the moves before resuming can't be undone. A board without
+    /// bombs hasn't had its first reveal yet.
+    pub fn resume(
+        config: Config,
+        seed: u64,
+        board: Board,
+        elapsed: Duration,
+        hints: u32,
+        moves: Vec<Move>,
+        undos: Option<u32>,
+    ) -> Game {
+        let armed = board.cells().any(|cell| cell.contents.is_bomb());
+        Game {
+            board,
+            config,
+            seed,
+            status: Status::Playing,
+            armed,
+            start: armed.then(Instant::now),
+            previous: elapsed,
+            finished: None,
+            exploded: None,
+            fell_back: false,
+            hints,
+            moves,
+            question_marks: false,
+            undos,
+            history: Vec::new(),
+            future: Vec::new(),
+        }
     }
 
-    fn draw(&self, printer: &cursive::Printer) {
-        let (r, c) = self.size;
-        for x in 0..r {
-            for y in 0..c {
-                let cell = &self[(x, y)];
-                let text = match cell.state {
-                    CellState::Flagged => "[~]",
-                    CellState::Hidden => "[#]",
-                    CellState::Revealed => match cell.contents {
-                        CellContents::Hint(n) => NUMBERS[n as usize],
-                        CellContents::Bomb => "[*]",
-                    },
-                };
-                printer.print((y * 3, x), text);
-            }
+    /// Whether the bombs have been placed, i.e. a cell has been revealed.
+    pub fn is_started(&self) -> bool {
+        self.armed
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The code that starts another game on this layout.
+    pub fn code(&self) -> GameCode {
+        GameCode {
+            config: self.config.clone(),
+            seed: self.seed,
         }
     }
 
-    fn required_size(&mut self, _: Vec2) -> Vec2 {
-        let (r, c) = self.size;
-        Vec2::new(c * 3, r)
+    /// Whether this is a no-guess game whose board may need guessing after
+    /// all, because none that didn't could be found in time.
+    pub fn fell_back(&self) -> bool {
+        self.fell_back
     }
 
-    fn layout(&mut self, _: Vec2) {}
+    pub fn hints(&self) -> u32 {
+        self.hints
+    }
 
-    fn needs_relayout(&self) -> bool {
-        true
+    /// Whether [`Game::toggle_flag`] goes on from a flag to a question mark
+    /// before clearing the cell.
+    pub fn set_question_marks(&mut self, on: bool) {
+        self.question_marks = on;
     }
 
-    fn call_on_any(&mut self, _: &cursive::view::Selector, _: cursive::event::AnyCb) {}
+    pub fn question_marks(&self) -> bool {
+        self.question_marks
+    }
 
-    fn focus_view(
-        &mut self,
-        _: &cursive::view::Selector,
-    ) -> Result<EventResult, cursive::view::ViewNotFound> {
-        Err(cursive::view::ViewNotFound)
+    /// Turns on practice mode, where moves can be undone.
+    pub fn enable_practice(&mut self) {
+        self.undos.get_or_insert(0);
     }
 
-    fn important_area(&self, view_size: Vec2) -> cursive::Rect {
-        cursive::Rect::from_size((0, 0), view_size)
+    pub fn is_practice(&self) -> bool {
+        self.undos.is_some()
     }
 
-    fn type_name(&self) -> &'static str {
-        std::any::type_name::<Self>()
+    /// How many moves have been undone. Always 0 outside practice mode.
+    pub fn undos(&self) -> u32 {
+        self.undos.unwrap_or(0)
     }
-}
 
-impl Grid {
-    fn new(size: (usize, usize)) -> Grid {
-        let (r, c) = size;
-        let cells = vec![Cell::default(); r * c];
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
 
-        Grid { size, cells }
+    pub fn status(&self) -> Status {
+        self.status
     }
 
-    fn reveal(&mut self, index: (usize, usize)) {
-        let cell = &mut self[index];
-        cell.state = CellState::Revealed;
+    /// The bomb that was revealed to lose the game.
+    pub fn exploded(&self) -> Option<Pos> {
+        self.exploded
+    }
 
-        if let CellContents::Hint(0) = cell.contents {
-            let mut stack = self.neighbors(index);
-            while let Some(current) = stack.pop() {
-                let current_cell = &mut self[current];
-                if let CellContents::Hint(0) = current_cell.contents {
-                    current_cell.state = CellState::Revealed;
-                    stack.append(&mut self.neighbors(current));
-                }
+    pub fn is_over(&self) -> bool {
+        self.status != Status::Playing
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        match (self.finished, self.start) {
+            (Some(time), _) => time,
+            (None, Some(start)) => self.previous + start.elapsed(),
+            (None, None) => self.previous,
+        }
+    }
+
+    /// Mines minus flags; negative when there are more flags than mines.
+    /// Question marks don't count.
+    pub fn mines_left(&self) -> i64 {
+        let flags: u32 = self.board.cells().map(|cell| cell.state.flags()).sum();
+        self.config.num_bombs as i64 - flags as i64
+    }
+
+    /// Reveals the hidden or question-marked cell at `index`. The first
+    /// reveal places the bombs.
+    pub fn reveal(&mut self, index: impl Into<Pos>) -> Status {
+        let index = index.into();
+        if self.is_over() || !self.board[index].state.is_covered() {
+            return self.status;
+        }
+        self.record(Action::Reveal, index);
+
+        if !self.armed {
+            self.arm(StdRng::seed_from_u64(self.seed), index);
+        } else if self.board[index].contents.is_bomb() {
+            return self.explode(index);
+        }
+        self.board.reveal(index);
+
+        self.check_cleared()
+    }
+
+    /// Right click: flags a hidden cell, and unflags it again or, with
+    /// question marks on, turns the flag into a question mark first. Where
+    /// cells can hold several mines, each click adds one to the flag until
+    /// it reaches the most a cell can hold.
+    pub fn toggle_flag(&mut self, index: impl Into<Pos>) {
+        let index = index.into();
+        if self.is_over() {
+            return;
+        }
+
+        let state = match self.board[index].state {
+            CellState::Hidden => CellState::Flagged(1),
+            CellState::Flagged(n) if n < self.config.max_mines => CellState::Flagged(n + 1),
+            CellState::Flagged(_) if self.question_marks => CellState::Questioned,
+            CellState::Flagged(_) | CellState::Questioned => CellState::Hidden,
+            CellState::Revealed => return,
+        };
+        self.record(Action::Flag, index);
+        self.board[index].state = state;
+    }
+
+    /// Reveals every hidden neighbor of the revealed hint at `index`, as long
+    /// as the mines flagged around it add up to the hint. Question marks
+    /// count as hidden. A misplaced flag means one of those neighbors is a
+    /// bomb, which ends the game.
+    pub fn chord(&mut self, index: impl Into<Pos>) -> Status {
+        let index = index.into();
+        let cell = &self.board[index];
+        let CellContents::Hint(n) = cell.contents else {
+            return self.status;
+        };
+        if self.is_over() || cell.state != CellState::Revealed {
+            return self.status;
+        }
+
+        let neighbors = self.board.neighbors(index);
+        let flags: u32 = neighbors.iter().map(|&i| self.board[i].state.flags()).sum();
+        if flags != n {
+            return self.status;
+        }
+        self.record(Action::Chord, index);
+
+        let hidden: Vec<_> = neighbors
+            .into_iter()
+            .filter(|&i| self.board[i].state.is_covered())
+            .collect();
+        if let Some(&bomb) = hidden.iter().find(|&&i| self.board[i].contents.is_bomb()) {
+            return self.explode(bomb);
+        }
+        for i in hidden {
+            self.board.reveal(i);
+        }
+
+        self.check_cleared()
+    }
+
+    /// Makes the move `action` at `index`, as the method of the same name
+    /// would.
+    pub fn apply(&mut self, action: Action, index: impl Into<Pos>) -> Status {
+        let index = index.into();
+        match action {
+            Action::Reveal => self.reveal(index),
+            Action::Flag => {
+                self.toggle_flag(index);
+                self.status
             }
+            Action::Chord => self.chord(index),
+        }
+    }
+
+    /// A move that the revealed hints and flags prove correct, counting it
+    /// against the player. `None` if there is no such move, or the game hasn't
+    /// started or is over.
+    pub fn hint(&mut self) -> Option<Hint> {
+        if !self.armed || self.is_over() {
+            return None;
         }
+        let hint = solver::hint(&self.board, self.config.num_bombs)?;
+        self.hints += 1;
+        Some(hint)
     }
 
-    fn neighbors(&self, index: (usize, usize)) -> Vec<(usize, usize)> {
-        let (r, c) = index;
-        let mut res = Vec::with_capacity(4);
-        if r > 0 {
-            res.push((r - 1, c));
+    /// Places the bombs, keeping the cells in [`Game::safe_zone`] clear. A
+    /// no-guess game keeps dealing fresh layouts until one is solvable from
+    /// `first`, or until [`NO_GUESS_BUDGET`] runs out, in which case it keeps
+    /// the last one.
+    fn arm<R: Rng>(&mut self, rng: R, first: Pos) {
+        let mut rng = rng;
+        let safe = self.safe_zone(first);
+        let deadline = Instant::now() + NO_GUESS_BUDGET;
+        let layout = loop {
+            let mut layout = self.config.board();
+            place_bombs_rnd(&mut rng, &mut layout, self.config.num_bombs, &safe);
 
-            if c > 0 {
-                res.push((r - 1, c - 1));
+            if !self.config.no_guess || is_solvable(&layout, self.config.num_bombs, first) {
+                break layout;
             }
+            if Instant::now() >= deadline {
+                self.fell_back = true;
+                break layout;
+            }
+        };
 
-            if r < self.size.0 - 1 {
-                res.push((r - 1, c + 1));
+        // Copy the bombs over, keeping any flags placed before the first click.
+        for i in layout.indices() {
+            for _ in 0..layout[i].contents.mines() {
+                self.board.place_bomb(i);
             }
         }
+        self.armed = true;
+        self.start = Some(Instant::now());
+    }
 
-        if c > 0 {
-            res.push((r, c - 1));
+    /// The cells that must stay clear when `first` is the first reveal:
+    /// `first` itself and, depending on `safe_start`, its neighbors. Boards
+    /// too crowded to spare the neighbors only keep `first` clear.
+    fn safe_zone(&self, first: Pos) -> Vec<Pos> {
+        let mut safe = vec![first];
+        if self.config.safe_start == SafeStart::Area {
+            safe.append(&mut self.board.neighbors(first));
         }
+        let room = (self.board.num_cells() - safe.len()) * self.config.max_mines as usize;
+        if room < self.config.num_bombs as usize {
+            safe.truncate(1);
+        }
+        safe
+    }
 
-        if c < self.size.1 - 1 {
-            res.push((r, c + 1));
+    /// Ends the game with a win once the last safe cell is revealed.
+    fn check_cleared(&mut self) -> Status {
+        if self.board.is_cleared() {
+            self.board.flag_bombs();
+            return self.finish(Status::Won);
         }
+        self.status
+    }
 
-        if r < self.size.0 - 1 {
-            res.push((r + 1, c));
+    /// Takes back the last move, even one that lost the game. Only in
+    /// practice mode, and not once the game is won. Returns whether there was
+    /// a move to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.status == Status::Won || self.undos.is_none() {
+            return false;
+        }
+        let Some(previous) = self.history.pop() else {
+            return false;
+        };
+        let current = self.restore(previous);
+        self.future.push(current);
+        self.undos = self.undos.map(|n| n + 1);
+        true
+    }
 
-            if c > 0 {
-                res.push((r + 1, c - 1));
-            }
+    /// Makes the last undone move again. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        let Some(next) = self.future.pop() else {
+            return false;
+        };
+        let current = self.restore(next);
+        self.history.push(current);
+        true
+    }
 
-            if c < self.size.1 + 1 {
-                res.push((r + 1, c + 1))
-            }
+    /// Rolls the game to `snapshot`, returning the state it replaced. The
+    /// clock keeps running through undos.
+    fn restore(&mut self, snapshot: Snapshot) -> Snapshot {
+        let current = self.snapshot();
+        self.board = snapshot.board;
+        self.status = snapshot.status;
+        self.armed = snapshot.armed;
+        self.finished = snapshot.finished;
+        self.exploded = snapshot.exploded;
+        self.moves = snapshot.moves;
+        current
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            status: self.status,
+            armed: self.armed,
+            finished: self.finished,
+            exploded: self.exploded,
+            moves: self.moves.clone(),
         }
+    }
 
-        res
+    /// Logs a move that is about to change the board, first saving the state
+    /// for undo in practice mode.
+    fn record(&mut self, action: Action, index: Pos) {
+        if self.is_practice() {
+            self.history.push(self.snapshot());
+            self.future.clear();
+        }
+        self.moves.push(Move {
+            action,
+            index,
+            at: self.elapsed(),
+        });
     }
-}
 
-impl Index<(usize, usize)> for Grid {
-    type Output = Cell;
+    /// Loses the game on the bomb at `index`.
+    fn explode(&mut self, index: Pos) -> Status {
+        self.board[index].state = CellState::Revealed;
+        self.exploded = Some(index);
+        self.finish(Status::Lost)
+    }
 
-    fn index(&self, index: (usize, usize)) -> &Self::Output {
-        &self.cells[index.0 * self.size.0 + index.1]
+    fn finish(&mut self, status: Status) -> Status {
+        self.finished = Some(self.elapsed());
+        self.status = status;
+        status
     }
 }
 
-impl IndexMut<(usize, usize)> for Grid {
-    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
-        &mut self.cells[index.0 * self.size.0 + index.1]
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A game with bombs at exactly `bombs`.
+    fn test_game(size: (usize, usize), bombs: &[(usize, usize)]) -> Game {
+        let mut game = Game::new(Config::custom(size, bombs.len() as u32));
+        for &i in bombs {
+            game.board.place_bomb(i);
+        }
+        game.armed = true;
+        game
     }
-}
 
-struct Config {
-    size: (usize, usize),
-    num_bombs: u32,
-}
+    #[test]
+    fn test_safe_zone() {
+        let mut game = Game::new(Config::custom((5, 5), 16));
+        assert_eq!(game.safe_zone((2, 2).into()).len(), 9);
 
-impl From<&Difficulty> for Config {
-    fn from(value: &Difficulty) -> Self {
-        match value {
-            Difficulty::Beginner => Config {
-                size: (9, 9),
-                num_bombs: 10,
-            },
-            Difficulty::Intermediate => Config {
-                size: (16, 16),
-                num_bombs: 40,
-            },
-            Difficulty::Expert => Config {
-                size: (16, 30),
-                num_bombs: 99,
-            },
+        game.config.safe_start = SafeStart::Cell;
+        assert_eq!(game.safe_zone((2, 2).into()), vec![(2, 2).into()]);
+
+        // Too crowded to keep the neighbors clear.
+        let game = Game::new(Config::custom((5, 5), 20));
+        assert_eq!(game.safe_zone((2, 2).into()), vec![(2, 2).into()]);
+    }
+
+    #[test]
+    fn test_layered_hints() {
+        // A cube of three layers, mined everywhere but the very middle.
+        let mut config = Config::custom((3, 3), 26);
+        config.layers = 3;
+        config.safe_start = SafeStart::Cell;
+        let mut game = Game::with_seed(config, 3);
+        let middle = Pos::new(1, 1, 1);
+        assert_eq!(game.reveal(middle), Status::Won);
+        assert_eq!(game.board()[middle].contents, CellContents::Hint(26));
+    }
+
+    #[test]
+    fn test_torus_safe_area_wraps() {
+        // Every cell but the corner and its eight wrapped neighbors is a mine,
+        // so the first reveal floods across the edges and wins.
+        let mut config = Config::custom((9, 9), 72);
+        config.torus = true;
+        let mut game = Game::with_seed(config, 7);
+        assert_eq!(game.reveal((0, 0)), Status::Won);
+        for i in [(8, 8), (8, 0), (0, 8), (1, 8), (8, 1)] {
+            assert_eq!(game.board()[i].state, CellState::Revealed, "{i:?}");
         }
     }
-}
 
-fn place_bombs_rnd<R: Rng>(rng: R, grid: &mut Grid, num_bombs: u32) {
-    let mut rng = rng;
-
-    let (r, c) = grid.size;
-    let mut bombs_placed = 0;
-    while bombs_placed < num_bombs {
-        let index = (rng.gen_range(0..r), rng.gen_range(0..c));
-        let cell = &mut grid[index];
-        if cell.contents != CellContents::Bomb {
-            cell.contents = CellContents::Bomb;
-
-            for neighbor in grid.neighbors(index) {
-                let neighbor_cell = &mut grid[neighbor];
-                if let CellContents::Hint(n) = neighbor_cell.contents {
-                    neighbor_cell.contents = CellContents::Hint(n + 1);
+    #[test]
+    fn test_chord() {
+        let mut game = test_game((5, 5), &[(1, 1), (1, 3), (3, 1), (3, 3)]);
+        game.reveal((2, 2));
+        assert_eq!(game.board[(2, 2)].contents, CellContents::Hint(4));
+
+        // Not enough flags: nothing happens.
+        for i in [(1, 1), (1, 3), (3, 1)] {
+            game.toggle_flag(i);
+        }
+        game.chord((2, 2));
+        assert_eq!(game.board[(1, 2)].state, CellState::Hidden);
+
+        game.toggle_flag((3, 3));
+        assert_eq!(game.chord((2, 2)), Status::Playing);
+        for i in [(1, 2), (2, 1), (2, 3), (3, 2)] {
+            assert_eq!(game.board[i].state, CellState::Revealed);
+        }
+
+        // A wrong flag detonates the bomb it was hiding.
+        game.toggle_flag((3, 1));
+        game.toggle_flag((2, 0));
+        assert_eq!(game.chord((2, 1)), Status::Lost);
+        assert!(game.is_over());
+        assert_eq!(game.exploded(), Some((3, 1).into()));
+        assert_eq!(game.board[(3, 1)].state, CellState::Revealed);
+    }
+
+    #[test]
+    fn test_question_marks() {
+        let mut game = test_game((3, 3), &[(0, 0)]);
+        game.toggle_flag((0, 1));
+        game.toggle_flag((0, 1));
+        assert_eq!(game.board[(0, 1)].state, CellState::Hidden);
+
+        game.set_question_marks(true);
+        game.toggle_flag((0, 0));
+        game.toggle_flag((0, 0));
+        assert_eq!(game.board[(0, 0)].state, CellState::Questioned);
+        assert_eq!(game.mines_left(), 1);
+
+        // A question mark isn't a flag, so chording around it does nothing.
+        game.reveal((1, 1));
+        assert_eq!(game.chord((1, 1)), Status::Playing);
+        assert_eq!(game.board[(0, 1)].state, CellState::Hidden);
+
+        game.toggle_flag((0, 0));
+        assert_eq!(game.board[(0, 0)].state, CellState::Hidden);
+        game.toggle_flag((0, 0));
+        assert_eq!(game.chord((1, 1)), Status::Won);
+    }
+
+    #[test]
+    fn test_stacked_flags_and_chord() {
+        let mut game = Game::new(Config {
+            max_mines: 3,
+            ..Config::custom((3, 3), 4)
+        });
+        for _ in 0..3 {
+            game.board.place_bomb((0, 0));
+        }
+        game.board.place_bomb((2, 2));
+        game.armed = true;
+
+        // Each right click adds a mine to the flag, up to three, then clears it.
+        let mut flags = Vec::new();
+        for _ in 0..4 {
+            game.toggle_flag((0, 0));
+            flags.push(game.board[(0, 0)].state.clone());
+        }
+        use CellState::*;
+        assert_eq!(flags, [Flagged(1), Flagged(2), Flagged(3), Hidden]);
+
+        // The 4 in the middle only chords once the flags add up to 4.
+        game.reveal((1, 1));
+        assert_eq!(game.board[(1, 1)].contents, CellContents::Hint(4));
+        for _ in 0..3 {
+            game.toggle_flag((0, 0));
+        }
+        assert_eq!(game.mines_left(), 1);
+        assert_eq!(game.chord((1, 1)), Status::Playing);
+        assert_eq!(game.board[(0, 1)].state, CellState::Hidden);
+        game.toggle_flag((2, 2));
+        assert_eq!(game.chord((1, 1)), Status::Won);
+        assert_eq!(game.mines_left(), 0);
+    }
+
+    #[test]
+    fn test_win_and_clock() {
+        let mut game = test_game((5, 5), &[(4, 4)]);
+        game.toggle_flag((0, 0));
+        assert_eq!(game.mines_left(), 0);
+        assert_eq!(game.elapsed(), Duration::ZERO);
+        game.toggle_flag((0, 0));
+
+        game.start = Some(Instant::now());
+        assert_eq!(game.reveal((0, 0)), Status::Won);
+        assert_eq!(game.board[(4, 4)].state, CellState::Flagged(1));
+
+        let time = game.elapsed();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(game.elapsed(), time);
+
+        // Finished games ignore further moves.
+        game.toggle_flag((4, 4));
+        assert_eq!(game.board[(4, 4)].state, CellState::Flagged(1));
+    }
+
+    #[test]
+    fn test_same_seed_same_board() {
+        let contents = |game: &Game| {
+            game.board
+                .cells()
+                .map(|cell| cell.contents.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut a = Game::with_seed(Config::custom((9, 9), 10), 3);
+        let mut b = Game::with_seed(Config::custom((9, 9), 10), 3);
+        a.reveal((4, 4));
+        b.reveal((4, 4));
+        assert_eq!(contents(&a), contents(&b));
+        assert_eq!(a.code().to_string(), "9x9-10a-3");
+    }
+
+    #[test]
+    fn test_no_guess() {
+        let mut config = Config::from(&Difficulty::Intermediate);
+        config.no_guess = true;
+        for seed in 0..5 {
+            let mut game = Game::with_seed(config.clone(), seed);
+            game.toggle_flag((0, 0));
+            game.reveal((8, 8));
+            assert!(!game.fell_back());
+            assert_eq!(game.board[(0, 0)].state, CellState::Flagged(1));
+
+            let mut layout = Board::new(config.size);
+            for r in 0..16 {
+                for c in 0..16 {
+                    if game.board[(r, c)].contents.is_bomb() {
+                        layout.place_bomb((r, c));
+                    }
                 }
             }
-            bombs_placed += 1;
+            assert!(is_solvable(&layout, config.num_bombs, (8, 8)));
         }
     }
-}
 
-fn new_game(s: &mut Cursive, d: &Difficulty) {
-    s.pop_layer();
+    #[test]
+    fn test_moves_replay() {
+        let mut game = Game::with_seed(Config::custom((9, 9), 10), 3);
+        game.toggle_flag((0, 0));
+        game.reveal((4, 4));
+        game.reveal((4, 4));
+        game.toggle_flag((0, 0));
+        let actions: Vec<_> = game.moves().iter().map(|m| m.action).collect();
+        assert_eq!(actions, [Action::Flag, Action::Reveal, Action::Flag]);
 
-    let config = Config::from(d);
-    let mut grid = Grid::new(config.size);
+        let mut copy = Game::with_seed(Config::custom((9, 9), 10), 3);
+        for m in game.moves() {
+            copy.apply(m.action, m.index);
+        }
+        for (a, b) in copy.board.cells().zip(game.board.cells()) {
+            assert_eq!((&a.contents, &a.state), (&b.contents, &b.state));
+        }
+    }
 
-    let rng = rand::thread_rng();
-    place_bombs_rnd(rng, &mut grid, config.num_bombs);
+    #[test]
+    fn test_undo_redo() {
+        let mut game = test_game((5, 5), &[(1, 1)]);
+        game.start = Some(Instant::now());
+        game.toggle_flag((4, 4));
+        assert!(!game.undo());
 
-    s.add_layer(Dialog::around(
-        LinearLayout::vertical()
-            .child(Panel::new(grid))
-            .child(Button::new("Quit", |s| s.quit())),
-    ));
-}
+        let mut game = test_game((5, 5), &[(1, 1)]);
+        game.enable_practice();
+        game.toggle_flag((4, 4));
+        assert_eq!(game.reveal((1, 1)), Status::Lost);
 
-#[cfg(test)]
-mod test {
-    use super::*;
+        // Take back the fatal click, then the flag.
+        assert_eq!(game.exploded(), Some((1, 1).into()));
+        assert!(game.undo());
+        assert_eq!(game.status(), Status::Playing);
+        assert_eq!(game.exploded(), None);
+        assert_eq!(game.board[(1, 1)].state, CellState::Hidden);
+        assert!(game.undo());
+        assert_eq!(game.board[(4, 4)].state, CellState::Hidden);
+        assert!(game.moves().is_empty());
+        assert!(!game.undo());
+        assert_eq!(game.undos(), 2);
+
+        assert!(game.redo());
+        assert_eq!(game.board[(4, 4)].state, CellState::Flagged(1));
+
+        // A new move drops what was left to redo.
+        game.reveal((0, 4));
+        assert!(!game.redo());
+        assert_eq!(game.moves().len(), 2);
+    }
+
+    #[test]
+    fn test_hint() {
+        let mut game = test_game((1, 5), &[(0, 4)]);
+        game.board[(0, 2)].state = CellState::Revealed;
+        assert_eq!(game.hint(), Some(Hint::Safe((0, 1).into())));
+        assert_eq!(game.hints(), 1);
+
+        for c in [0, 1, 3] {
+            game.board[(0, c)].state = CellState::Revealed;
+        }
+        assert_eq!(game.hint(), Some(Hint::Mine((0, 4).into(), 1)));
+        game.toggle_flag((0, 4));
+        assert_eq!(game.hint(), None);
+        assert_eq!(game.hints(), 2);
+    }
+
+    #[test]
+    fn test_validate_config() {
+        assert!(Config::custom((9, 9), 10).validate().is_ok());
+        assert!(Config::custom((1, 2), 1).validate().is_ok());
+        assert_eq!(
+            Config::custom((0, 9), 1).validate(),
+            Err(ConfigError::Empty)
+        );
+        assert_eq!(
+            Config::custom((9, 0), 1).validate(),
+            Err(ConfigError::Empty)
+        );
+        assert_eq!(
+            Config::custom((9, 9), 0).validate(),
+            Err(ConfigError::NoMines)
+        );
+        assert_eq!(
+            Config::custom((9, 9), 81).validate(),
+            Err(ConfigError::TooManyMines { max: 80 })
+        );
+
+        let mut torus = Config::custom((3, 3), 1);
+        torus.torus = true;
+        assert!(torus.validate().is_ok());
+        torus.size = (2, 9);
+        assert_eq!(torus.validate(), Err(ConfigError::TooSmallForTorus));
+
+        torus.size = (9, 9);
+        torus.shape = Shape::Hex;
+        assert_eq!(torus.validate(), Err(ConfigError::OddHexTorus));
+        torus.size = (8, 9);
+        assert!(torus.validate().is_ok());
+
+        let stacked = Config {
+            max_mines: 3,
+            ..Config::custom((3, 3), 24)
+        };
+        assert!(stacked.validate().is_ok());
+        assert_eq!(
+            Config {
+                num_bombs: 25,
+                ..stacked.clone()
+            }
+            .validate(),
+            Err(ConfigError::TooManyMines { max: 24 })
+        );
+        assert_eq!(
+            Config {
+                max_mines: 4,
+                ..stacked
+            }
+            .validate(),
+            Err(ConfigError::MinesPerCell)
+        );
+
+        let mut layered = Config::custom((3, 3), 26);
+        layered.layers = 3;
+        assert!(layered.validate().is_ok());
+        layered.num_bombs = 27;
+        assert_eq!(
+            layered.validate(),
+            Err(ConfigError::TooManyMines { max: 26 })
+        );
+        layered.num_bombs = 10;
+        layered.max_mines = 2;
+        assert_eq!(layered.validate(), Err(ConfigError::StackedLayers));
+        layered.layers = 0;
+        assert_eq!(layered.validate(), Err(ConfigError::Empty));
+        layered.layers = MAX_LAYERS + 1;
+        assert_eq!(layered.validate(), Err(ConfigError::TooManyLayers));
+        let code: GameCode = "9x9x100000000-10a-1".parse().unwrap();
+        assert_eq!(code.config.validate(), Err(ConfigError::TooManyLayers));
+
+        let huge = Config::custom((1 << 33, 1 << 33), 10);
+        assert_eq!(huge.validate(), Err(ConfigError::TooBig));
+    }
 
     #[test]
-    fn test_neighbors() {
-        let size = (5, 5);
-        let grid = Grid::new(size);
+    fn test_resume() {
+        let mut game = test_game((5, 5), &[(1, 1)]);
+        game.start = Some(Instant::now());
+        game.reveal((0, 0));
+        game.toggle_flag((1, 1));
+
+        let resumed = Game::resume(
+            game.config.clone(),
+            game.seed,
+            game.board.clone(),
+            Duration::from_secs(30),
+            2,
+            game.moves.clone(),
+            None,
+        );
+        assert!(resumed.is_started());
+        assert!(resumed.elapsed() >= Duration::from_secs(30));
+        assert_eq!(resumed.mines_left(), 0);
+        assert_eq!(resumed.hints(), 2);
+        assert_eq!(resumed.moves().len(), 2);
+        assert_eq!(resumed.board[(0, 0)].state, CellState::Revealed);
+
+        let fresh = Game::resume(
+            game.config.clone(),
+            1,
+            Board::new((5, 5)),
+            Duration::ZERO,
+            0,
+            Vec::new(),
+            Some(1),
+        );
+        assert!(!fresh.is_started());
+        assert!(fresh.is_practice());
+        assert_eq!(fresh.undos(), 1);
+        assert_eq!(fresh.elapsed(), Duration::ZERO);
     }
 }
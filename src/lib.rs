@@ -0,0 +1,18 @@
+//! The rules of Minesweeper, independent of any front end.
+
+mod board;
+mod code;
+mod game;
+mod probability;
+mod solver;
+mod topology;
+
+pub use board::{place_bombs_rnd, Board, Cell, CellContents, CellState};
+pub use code::{GameCode, ParseCodeError};
+pub use game::{
+    Action, Config, ConfigError, Difficulty, Game, Move, SafeStart, Status, MAX_LAYERS,
+    MAX_MINES_PER_CELL,
+};
+pub use probability::{probabilities, OddsError, Probabilities};
+pub use solver::{deduce, flags_add_up, hint, is_solvable, Deductions, Hint};
+pub use topology::{Pos, Shape, Topology};
@@ -1,7 +1,125 @@
-mod game;
+use std::process::ExitCode;
+
+mod replay;
+mod save;
+mod scores;
+mod settings;
+mod storage;
+mod theme;
+mod ui;
+
+use settings::Settings;
+use theme::Theme;
+
+const USAGE: &str = "usage: mines [--seed <number>] [--theme <name or file>]";
+
+/// What the command line asked for.
+struct Args {
+    seed: Option<u64>,
+    /// Overrides the theme from the settings.
+    theme: Option<String>,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut res = Args {
+        seed: None,
+        theme: None,
+    };
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+
+        match flag.as_str() {
+            "--seed" => {
+                let value = value
+                    .or_else(|| args.next())
+                    .ok_or("--seed needs a value")?;
+                let seed = value
+                    .parse()
+                    .map_err(|_| format!("invalid seed: {value}"))?;
+                res.seed = Some(seed);
+            }
+            "--theme" => {
+                res.theme = Some(
+                    value
+                        .or_else(|| args.next())
+                        .ok_or("--theme needs a value")?,
+                );
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument: {flag}\n{USAGE}")),
+        }
+    }
+
+    Ok(res)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Bad settings are reported once the menu is up, and left at their
+    // defaults, rather than keeping the game from starting.
+    let (settings, mut problems) =
+        settings::load().unwrap_or_else(|e| (Settings::default(), vec![e.to_string()]));
+    let theme = match &args.theme {
+        Some(name) => match theme::load(name) {
+            Ok(theme) => theme,
+            Err(e) => {
+                eprintln!("could not load theme: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => theme::load(&settings.theme).unwrap_or_else(|e| {
+            problems.push(format!("theme: {e}"));
+            Theme::default()
+        }),
+    };
 
-fn main() {
     let mut siv = cursive::default();
-    game::start_menu(&mut siv);
+    siv.set_user_data(ui::Options {
+        seed: args.seed,
+        settings,
+        theme,
+    });
+    ui::start_menu(&mut siv);
+    if !problems.is_empty() {
+        ui::report_settings(&mut siv, &problems);
+    }
     siv.run();
+
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&[]).unwrap().seed, None);
+        assert_eq!(parse(&["--seed", "42"]).unwrap().seed, Some(42));
+        assert_eq!(parse(&["--seed=7"]).unwrap().seed, Some(7));
+        assert!(parse(&["--seed"]).is_err());
+        assert!(parse(&["--seed", "-1"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert_eq!(
+            parse(&["--theme", "mono"]).unwrap().theme.as_deref(),
+            Some("mono")
+        );
+        assert!(parse(&["--theme"]).is_err());
+    }
 }
@@ -0,0 +1,403 @@
+//! Exact mine probabilities for the hidden cells, given the revealed hints,
+//! the flags and the total number of mines.
+//!
+//! Hidden cells next to a hint (the frontier) are split into independent
+//! components, each of which is enumerated on its own. The remaining hidden
+//! cells (the interior) are interchangeable, so they only need counting: a
+//! way of placing `m` mines on the frontier leaves `C(interior, left - m)`
+//! ways of placing the rest.
+
+use std::collections::HashMap;
+
+use crate::{
+    board::{Board, CellState},
+    solver::{hint_constraints, Constraint},
+    topology::{Pos, Topology},
+};
+
+/// Enumeration gives up after this many steps, summed over all components.
+const MAX_STEPS: usize = 2_000_000;
+
+/// The chance of a mine under each hidden cell.
+#[derive(Debug, Clone)]
+pub struct Probabilities {
+    topology: Topology,
+    cells: Vec<Option<f64>>,
+}
+
+impl Probabilities {
+    /// `None` for cells that aren't hidden.
+    pub fn get(&self, index: impl Into<Pos>) -> Option<f64> {
+        self.cells[self.topology.offset(index)]
+    }
+
+    fn set(&mut self, index: Pos, p: f64) {
+        let offset = self.topology.offset(index);
+        self.cells[offset] = Some(p);
+    }
+}
+
+/// Why there are no probabilities for a board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OddsError {
+    /// Cells can hold more than one mine, which this doesn't know how to
+    /// count.
+    Stacked,
+    /// No placement of the mines fits what's on show, say because of a flag
+    /// in the wrong place.
+    Contradiction,
+    /// Enumerating the placements would take too long.
+    TooManyPossibilities,
+}
+
+/// The ways a component's mines can be placed, grouped by how many mines
+/// they use.
+struct Component {
+    cells: Vec<Pos>,
+    /// `ways[k]`: placements with `k` mines.
+    ways: Vec<f64>,
+    /// `mines[k][j]`: placements with `k` mines that put one on `cells[j]`.
+    mines: Vec<Vec<f64>>,
+}
+
+/// Works out the probabilities, trusting flags to be on mines.
+pub fn probabilities(board: &Board, num_bombs: u32) -> Result<Probabilities, OddsError> {
+    if board.max_mines() > 1 {
+        return Err(OddsError::Stacked);
+    }
+    let hints = hint_constraints(board).ok_or(OddsError::Contradiction)?;
+
+    let mut hidden = Vec::new();
+    let mut flags = 0;
+    for i in board.indices() {
+        match board[i].state {
+            CellState::Hidden | CellState::Questioned => hidden.push(i),
+            CellState::Flagged(_) => flags += 1,
+            CellState::Revealed => (),
+        }
+    }
+    let left = (num_bombs as usize)
+        .checked_sub(flags)
+        .ok_or(OddsError::Contradiction)?;
+
+    let mut steps = 0;
+    let mut components = Vec::new();
+    for group in components_of(&hints) {
+        components.push(enumerate(&group, &mut steps)?);
+    }
+    let frontier: usize = components.iter().map(|k| k.cells.len()).sum();
+    let interior = hidden.len() - frontier;
+
+    let ln_binom = ln_binomials(interior);
+    // ln C(interior, n), or None when that many mines don't fit.
+    let rest = |n: isize| -> Option<f64> {
+        (0..=interior as isize)
+            .contains(&n)
+            .then(|| ln_binom[n as usize])
+    };
+
+    let all = components
+        .iter()
+        .fold(vec![1.0], |acc, k| convolve(&acc, &k.ways));
+    // Weights are scaled by exp(-offset) to stay within range.
+    let offset = all
+        .iter()
+        .enumerate()
+        .filter(|(_, &w)| w > 0.0)
+        .filter_map(|(m, w)| Some(w.ln() + rest(left as isize - m as isize)?))
+        .fold(f64::NEG_INFINITY, f64::max);
+    if offset == f64::NEG_INFINITY {
+        return Err(OddsError::Contradiction);
+    }
+    let weight = |ways: f64, mines: usize| -> f64 {
+        match rest(left as isize - mines as isize) {
+            Some(ln) if ways > 0.0 => (ways.ln() + ln - offset).exp(),
+            _ => 0.0,
+        }
+    };
+
+    let total: f64 = all.iter().enumerate().map(|(m, &w)| weight(w, m)).sum();
+    let mut res = Probabilities {
+        topology: *board.topology(),
+        cells: vec![None; board.num_cells()],
+    };
+
+    for (i, component) in components.iter().enumerate() {
+        let others = components
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold(vec![1.0], |acc, (_, k)| convolve(&acc, &k.ways));
+
+        for (j, &cell) in component.cells.iter().enumerate() {
+            let mut sum = 0.0;
+            for (k, mines) in component.mines.iter().enumerate() {
+                for (m, &w) in others.iter().enumerate() {
+                    sum += weight(mines[j] * w, k + m);
+                }
+            }
+            res.set(cell, sum / total);
+        }
+    }
+
+    if interior > 0 {
+        let expected: f64 = all
+            .iter()
+            .enumerate()
+            .map(|(m, &w)| weight(w, m) * left.saturating_sub(m) as f64)
+            .sum();
+        let p = expected / total / interior as f64;
+        for &cell in &hidden {
+            if res.get(cell).is_none() {
+                res.set(cell, p);
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Groups the hints into sets that share no cells with each other.
+fn components_of(hints: &[Constraint]) -> Vec<Vec<&Constraint>> {
+    let mut by_cell: HashMap<Pos, Vec<usize>> = HashMap::new();
+    for (i, k) in hints.iter().enumerate() {
+        for &cell in &k.cells {
+            by_cell.entry(cell).or_default().push(i);
+        }
+    }
+
+    let mut seen = vec![false; hints.len()];
+    let mut res = Vec::new();
+    for start in 0..hints.len() {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut group = Vec::new();
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            group.push(&hints[i]);
+            for cell in &hints[i].cells {
+                for &j in &by_cell[cell] {
+                    if !seen[j] {
+                        seen[j] = true;
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+        res.push(group);
+    }
+    res
+}
+
+/// Counts every placement of mines on the cells of `hints` that satisfies
+/// them all, by backtracking. Fails if there are none, or if `steps` passes
+/// [`MAX_STEPS`].
+fn enumerate(hints: &[&Constraint], steps: &mut usize) -> Result<Component, OddsError> {
+    // Cells in the order the hints list them, so that each hint is settled
+    // soon after its first cell is tried.
+    let mut cells: Vec<Pos> = Vec::new();
+    for k in hints {
+        for &cell in &k.cells {
+            if !cells.contains(&cell) {
+                cells.push(cell);
+            }
+        }
+    }
+    let by_cell: Vec<Vec<usize>> = cells
+        .iter()
+        .map(|cell| {
+            (0..hints.len())
+                .filter(|&i| hints[i].cells.binary_search(cell).is_ok())
+                .collect()
+        })
+        .collect();
+
+    struct Search<'a> {
+        hints: &'a [&'a Constraint],
+        by_cell: Vec<Vec<usize>>,
+        /// Mines placed and cells still open, per hint.
+        placed: Vec<usize>,
+        open: Vec<usize>,
+        assignment: Vec<bool>,
+        ways: Vec<f64>,
+        mines: Vec<Vec<f64>>,
+    }
+
+    impl Search<'_> {
+        fn fits(&self, i: usize) -> bool {
+            let need = self.hints[i].mines;
+            self.placed[i] <= need && self.placed[i] + self.open[i] >= need
+        }
+
+        fn run(&mut self, j: usize, count: usize, steps: &mut usize) -> Option<()> {
+            *steps += 1;
+            if *steps > MAX_STEPS {
+                return None;
+            }
+            if j == self.assignment.len() {
+                self.ways[count] += 1.0;
+                for (n, &mine) in self.assignment.iter().enumerate() {
+                    if mine {
+                        self.mines[count][n] += 1.0;
+                    }
+                }
+                return Some(());
+            }
+
+            for mine in [false, true] {
+                self.assignment[j] = mine;
+                for &i in &self.by_cell[j] {
+                    self.open[i] -= 1;
+                    self.placed[i] += mine as usize;
+                }
+                if self.by_cell[j].iter().all(|&i| self.fits(i)) {
+                    self.run(j + 1, count + mine as usize, steps)?;
+                }
+                for &i in &self.by_cell[j] {
+                    self.open[i] += 1;
+                    self.placed[i] -= mine as usize;
+                }
+            }
+            Some(())
+        }
+    }
+
+    let n = cells.len();
+    let mut search = Search {
+        hints,
+        by_cell,
+        placed: vec![0; hints.len()],
+        open: hints.iter().map(|k| k.cells.len()).collect(),
+        assignment: vec![false; n],
+        ways: vec![0.0; n + 1],
+        mines: vec![vec![0.0; n]; n + 1],
+    };
+    search
+        .run(0, 0, steps)
+        .ok_or(OddsError::TooManyPossibilities)?;
+    if search.ways.iter().all(|&w| w == 0.0) {
+        return Err(OddsError::Contradiction);
+    }
+
+    Ok(Component {
+        cells,
+        ways: search.ways,
+        mines: search.mines,
+    })
+}
+
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut res = vec![0.0; a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            res[i + j] += x * y;
+        }
+    }
+    res
+}
+
+/// `ln C(n, k)` for every `k` from 0 to `n`.
+fn ln_binomials(n: usize) -> Vec<f64> {
+    let mut res = Vec::with_capacity(n + 1);
+    let mut ln = 0.0;
+    res.push(ln);
+    for k in 1..=n {
+        ln += ((n - k + 1) as f64).ln() - (k as f64).ln();
+        res.push(ln);
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{board::CellContents, game::Config, Difficulty, Game};
+
+    fn close(a: Option<f64>, b: f64) -> bool {
+        a.is_some_and(|a| (a - b).abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_frontier_and_interior() {
+        // A 1 in the corner of a 1x4 strip: its only neighbor is the mine.
+        let mut board = Board::new((1, 4));
+        board.place_bomb((0, 1));
+        board[(0, 0)].state = CellState::Revealed;
+
+        let odds = probabilities(&board, 1).unwrap();
+        assert!(close(odds.get((0, 1)), 1.0));
+        assert!(close(odds.get((0, 2)), 0.0));
+        assert_eq!(odds.get((0, 0)), None);
+
+        // With a second mine somewhere in the interior.
+        let odds = probabilities(&board, 2).unwrap();
+        assert!(close(odds.get((0, 3)), 0.5));
+    }
+
+    #[test]
+    fn test_weighs_by_interior() {
+        // A 1-row board: # 1 # 1 # # # #, with two mines. Either the middle
+        // cell is a mine and the other is among the three on the right, or
+        // the cells either side of it are both mines and the right is clear.
+        // The first happens in three ways, the second in one.
+        let mut board = Board::new((1, 8));
+        board.place_bomb((0, 2));
+        board.place_bomb((0, 6));
+        board[(0, 1)].state = CellState::Revealed;
+        board[(0, 3)].state = CellState::Revealed;
+        assert_eq!(board[(0, 3)].contents, CellContents::Hint(1));
+
+        let odds = probabilities(&board, 2).unwrap();
+        assert!(close(odds.get((0, 2)), 0.75));
+        assert!(close(odds.get((0, 0)), 0.25));
+        assert!(close(odds.get((0, 4)), 0.25));
+        assert!(close(odds.get((0, 7)), 0.25));
+    }
+
+    #[test]
+    fn test_contradiction() {
+        // The 1 needs a mine next to it, but the only mine is flagged
+        // elsewhere.
+        let mut board = Board::new((1, 3));
+        board.place_bomb((0, 1));
+        board[(0, 0)].state = CellState::Revealed;
+        board[(0, 2)].state = CellState::Flagged(1);
+        assert_eq!(
+            probabilities(&board, 1).unwrap_err(),
+            OddsError::Contradiction
+        );
+    }
+
+    #[test]
+    fn test_overflagged_hint() {
+        // The 1 has two flags next to it, so one of them is wrong, and the
+        // mine under the other cell can't be called clear.
+        let mut board = Board::new((2, 5));
+        board.place_bomb((1, 1));
+        board.place_bomb((1, 4));
+        board[(0, 1)].state = CellState::Revealed;
+        board[(0, 0)].state = CellState::Flagged(1);
+        board[(0, 2)].state = CellState::Flagged(1);
+        assert_eq!(
+            probabilities(&board, 3).unwrap_err(),
+            OddsError::Contradiction
+        );
+    }
+
+    #[test]
+    fn test_expert_sums_to_mines_left() {
+        for seed in 0..5 {
+            let mut game = Game::with_seed(Config::from(&Difficulty::Expert), seed);
+            game.reveal((8, 15));
+            let board = game.board();
+
+            let odds = probabilities(board, 99).unwrap();
+            let sum: f64 = (0..16)
+                .flat_map(|r| (0..30).map(move |c| (r, c)))
+                .filter_map(|i| odds.get(i))
+                .sum();
+            assert!((sum - 99.0).abs() < 1e-6, "{sum}");
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use mines::{Action, Board, Config, Game, GameCode, Move, Pos};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    save::{board_from_mines, mine_rows},
+    storage::{self, Error},
+};
+
+/// Bumped whenever the format changes, in step with the saved game format,
+/// whose version explains what changed and which versions are accepted.
+const VERSION: u32 = 2;
+
+/// The last finished game is always recorded here.
+const FILE_NAME: &str = "replay.toml";
+
+/// The on-disk form of a [`Replay`].
+#[derive(Debug, Serialize, Deserialize)]
+struct ReplayFile {
+    version: u32,
+    /// The [`GameCode`].
+    code: String,
+    /// The mine layout, as in a saved game. Kept alongside the seed because
+    /// no-guess boards depend on how long generation was allowed to take.
+    mines: Vec<String>,
+    /// Whether right click cycled through question marks, which changes what
+    /// a flag move does.
+    #[serde(default)]
+    question_marks: bool,
+    moves: Vec<MoveRecord>,
+}
+
+/// One move in a replay or saved game file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveRecord {
+    /// Milliseconds on the game clock.
+    ms: u64,
+    action: String,
+    /// Always 0 on boards with a single layer, and missing from files
+    /// written before there could be more.
+    #[serde(default)]
+    layer: usize,
+    row: usize,
+    col: usize,
+}
+
+impl From<&Move> for MoveRecord {
+    fn from(value: &Move) -> Self {
+        MoveRecord {
+            ms: value.at.as_millis() as u64,
+            action: match value.action {
+                Action::Reveal => "reveal",
+                Action::Flag => "flag",
+                Action::Chord => "chord",
+            }
+            .to_string(),
+            layer: value.index.layer,
+            row: value.index.row,
+            col: value.index.col,
+        }
+    }
+}
+
+impl MoveRecord {
+    /// The move, or `None` if it names an unknown action or a cell off a
+    /// board of `config`'s size.
+    pub fn to_move(&self, config: &Config) -> Option<Move> {
+        let action = match self.action.as_str() {
+            "reveal" => Action::Reveal,
+            "flag" => Action::Flag,
+            "chord" => Action::Chord,
+            _ => return None,
+        };
+        let index = Pos::new(self.layer, self.row, self.col);
+        config.topology().contains(index).then_some(Move {
+            action,
+            index,
+            at: Duration::from_millis(self.ms),
+        })
+    }
+}
+
+/// A finished game that can be played back move by move.
+pub struct Replay {
+    pub code: GameCode,
+    /// The mines, with every cell hidden.
+    board: Board,
+    question_marks: bool,
+    pub moves: Vec<Move>,
+}
+
+impl Replay {
+    /// The game as it was before the first move.
+    pub fn start(&self) -> Game {
+        let mut game = Game::resume(
+            self.code.config.clone(),
+            self.code.seed,
+            self.board.clone(),
+            Duration::ZERO,
+            0,
+            Vec::new(),
+            None,
+        );
+        game.set_question_marks(self.question_marks);
+        game
+    }
+
+    /// How long the game took, as far as the moves show.
+    pub fn duration(&self) -> Duration {
+        self.moves.last().map_or(Duration::ZERO, |m| m.at)
+    }
+}
+
+pub fn path() -> Result<PathBuf, Error> {
+    storage::data_file(FILE_NAME)
+}
+
+/// Records `game` as the latest replay.
+pub fn save(game: &Game) -> Result<(), Error> {
+    storage::write(&path()?, &encode(game))
+}
+
+pub fn load(path: &Path) -> Result<Replay, Error> {
+    match storage::read(path)? {
+        Some(text) => parse(&text),
+        None => Err(Error::Invalid(format!(
+            "there is no replay at {}",
+            path.display()
+        ))),
+    }
+}
+
+fn parse(text: &str) -> Result<Replay, Error> {
+    decode(toml::from_str(text).map_err(Error::Corrupt)?)
+}
+
+fn encode(game: &Game) -> ReplayFile {
+    ReplayFile {
+        version: VERSION,
+        code: game.code().to_string(),
+        mines: mine_rows(game.board()),
+        question_marks: game.question_marks(),
+        moves: game.moves().iter().map(MoveRecord::from).collect(),
+    }
+}
+
+fn decode(file: ReplayFile) -> Result<Replay, Error> {
+    let invalid = |e: &str| Error::Invalid(format!("replay is invalid: {e}"));
+
+    if !(1..=VERSION).contains(&file.version) {
+        return Err(Error::Invalid(format!(
+            "replay has version {}, expected {VERSION} or older",
+            file.version
+        )));
+    }
+
+    let code: GameCode = file.code.parse().map_err(|_| invalid("bad game code"))?;
+    if code.config.validate().is_err() || file.mines.is_empty() {
+        return Err(invalid("board size doesn't match"));
+    }
+    let board = board_from_mines(&code.config, &file.mines).map_err(invalid)?;
+    let moves: Vec<_> = file
+        .moves
+        .iter()
+        .map(|m| m.to_move(&code.config))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("bad move"))?;
+    if moves.windows(2).any(|w| w[1].at < w[0].at) {
+        return Err(invalid("moves are out of order"));
+    }
+
+    Ok(Replay {
+        code,
+        board,
+        question_marks: file.question_marks,
+        moves,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use mines::{Difficulty, Status};
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut game = Game::with_seed(Config::from(&Difficulty::Beginner), 3);
+        game.toggle_flag((0, 0));
+        game.reveal((4, 4));
+        game.toggle_flag((0, 0));
+
+        let text = toml::to_string(&encode(&game)).unwrap();
+        let replay = parse(&text).unwrap();
+        assert_eq!(replay.code, game.code());
+        assert_eq!(replay.moves.len(), 3);
+
+        let mut copy = replay.start();
+        for m in &replay.moves {
+            assert_eq!(copy.apply(m.action, m.index), Status::Playing);
+        }
+        for (a, b) in copy.board().cells().zip(game.board().cells()) {
+            assert_eq!((&a.contents, &a.state), (&b.contents, &b.state));
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_replays() {
+        let mut game = Game::with_seed(Config::from(&Difficulty::Beginner), 3);
+        game.reveal((4, 4));
+
+        let mut file = encode(&game);
+        file.moves[0].action = "explode".to_string();
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+
+        let mut file = encode(&game);
+        file.moves[0].col = 9;
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+
+        let mut file = encode(&game);
+        file.mines.clear();
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+
+        // Far too big to make a board for, so the rows have to be checked
+        // first.
+        let mut file = encode(&game);
+        file.code = "100000x100000-1a-1".to_string();
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+        let mut file = encode(&game);
+        file.code = "9x100000000000-1a-1".to_string();
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+
+        let mut file = encode(&game);
+        file.version = VERSION + 1;
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+        let mut file = encode(&game);
+        file.version = 1;
+        assert!(decode(file).is_ok());
+
+        assert!(matches!(parse("moves = 1"), Err(Error::Corrupt(_))));
+    }
+}
@@ -0,0 +1,386 @@
+use std::{fs, io, path::PathBuf, time::Duration};
+
+use mines::{Board, CellContents, CellState, Config, Difficulty, Game, GameCode, Pos, Topology};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    replay::MoveRecord,
+    storage::{self, Error},
+};
+
+/// Bumped whenever the format changes; saves from newer versions are refused,
+/// older ones are still accepted. Version 2 added cells holding several mines
+/// and the longer game codes that go with them. Replays follow the same
+/// history.
+const VERSION: u32 = 2;
+
+const FILE_NAME: &str = "save.toml";
+
+/// The on-disk form of a [`SavedGame`].
+#[derive(Debug, Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    /// The [`GameCode`].
+    code: String,
+    difficulty: Option<String>,
+    fixed_seed: bool,
+    elapsed_ms: u64,
+    #[serde(default)]
+    hints: u32,
+    /// Undos used so far, for practice games only.
+    #[serde(default)]
+    undos: Option<u32>,
+    /// One string per row, as written by [`mine_rows`]. Empty if no cell had
+    /// been revealed yet.
+    mines: Vec<String>,
+    /// One string per row laid out like [`mine_rows`], `#` for hidden, `o`
+    /// for revealed, `F` for flagged, `2` or `3` for flagged with that many
+    /// mines, and `?` for question-marked.
+    cells: Vec<String>,
+    #[serde(default)]
+    moves: Vec<MoveRecord>,
+}
+
+/// An unfinished game, along with how it was started.
+pub struct SavedGame {
+    pub game: Game,
+    pub difficulty: Option<Difficulty>,
+    /// Whether the seed came from `--seed` or a game code.
+    pub fixed_seed: bool,
+}
+
+pub fn path() -> Result<PathBuf, Error> {
+    storage::data_file(FILE_NAME)
+}
+
+pub fn exists() -> bool {
+    path().is_ok_and(|path| path.exists())
+}
+
+pub fn save(saved: &SavedGame) -> Result<(), Error> {
+    storage::write(&path()?, &encode(saved))
+}
+
+/// Reads the saved game, or `None` if there isn't one.
+pub fn load() -> Result<Option<SavedGame>, Error> {
+    match storage::read(&path()?)? {
+        Some(text) => parse(&text).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Deletes the saved game, so it can only be resumed once.
+pub fn discard() -> Result<(), Error> {
+    match fs::remove_file(path()?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+fn parse(text: &str) -> Result<SavedGame, Error> {
+    decode(toml::from_str(text).map_err(Error::Corrupt)?)
+}
+
+fn encode(saved: &SavedGame) -> SaveFile {
+    let board = saved.game.board();
+
+    SaveFile {
+        version: VERSION,
+        code: saved.game.code().to_string(),
+        difficulty: saved.difficulty.map(|d| d.name().to_string()),
+        fixed_seed: saved.fixed_seed,
+        elapsed_ms: saved.game.elapsed().as_millis() as u64,
+        hints: saved.game.hints(),
+        undos: saved.game.is_practice().then(|| saved.game.undos()),
+        mines: if saved.game.is_started() {
+            mine_rows(board)
+        } else {
+            Vec::new()
+        },
+        cells: rows(board, |i| match board[i].state {
+            CellState::Hidden => '#',
+            CellState::Revealed => 'o',
+            CellState::Flagged(1) => 'F',
+            CellState::Flagged(n) => count(n),
+            CellState::Questioned => '?',
+        }),
+        moves: saved.game.moves().iter().map(MoveRecord::from).collect(),
+    }
+}
+
+/// The digit for a number of mines, past the first.
+fn count(n: u32) -> char {
+    char::from_digit(n, 10).unwrap_or('?')
+}
+
+/// One string per row, holding that row of every layer, side by side with a
+/// space in between.
+fn rows(board: &Board, f: impl Fn(Pos) -> char) -> Vec<String> {
+    let (r, c) = board.size();
+    let layers = board.topology().layers();
+    (0..r)
+        .map(|x| {
+            let layer = |l| (0..c).map(|y| f(Pos::new(l, x, y))).collect::<String>();
+            (0..layers).map(layer).collect::<Vec<_>>().join(" ")
+        })
+        .collect()
+}
+
+/// Every cell in rows written by [`rows`], or `None` if they don't have the
+/// shape of `topology`.
+fn read_rows(topology: &Topology, rows: &[String]) -> Option<Vec<(Pos, char)>> {
+    let (r, c) = topology.size();
+    if rows.len() != r {
+        return None;
+    }
+    let mut cells = Vec::new();
+    for (x, row) in rows.iter().enumerate() {
+        let layers: Vec<_> = row.split(' ').collect();
+        if layers.len() != topology.layers() {
+            return None;
+        }
+        for (l, layer) in layers.into_iter().enumerate() {
+            if layer.chars().count() != c {
+                return None;
+            }
+            cells.extend(
+                layer
+                    .chars()
+                    .enumerate()
+                    .map(|(y, ch)| (Pos::new(l, x, y), ch)),
+            );
+        }
+    }
+    Some(cells)
+}
+
+/// The bombs on `board`, one string per row with `*` for a mine, `2` or `3`
+/// for a cell holding that many, and `.` otherwise. Rows of further layers
+/// follow on the same line, after a space.
+pub fn mine_rows(board: &Board) -> Vec<String> {
+    rows(board, |i| match board[i].contents {
+        CellContents::Bomb(1) => '*',
+        CellContents::Bomb(n) => count(n),
+        CellContents::Hint(_) => '.',
+    })
+}
+
+/// A board for `config` with the bombs from [`mine_rows`], checking that
+/// they fit before making room for it. No rows means no bombs yet.
+pub fn board_from_mines(config: &Config, mines: &[String]) -> Result<Board, &'static str> {
+    if mines.is_empty() {
+        return Ok(config.board());
+    }
+    let cells = read_rows(&config.topology(), mines).ok_or("board size doesn't match")?;
+    let mut board = config.board();
+    for (i, ch) in cells {
+        let n = match ch {
+            '*' => 1,
+            '.' => 0,
+            '2' | '3' => ch.to_digit(10).unwrap(),
+            _ => return Err("unknown mine marker"),
+        };
+        for _ in 0..n {
+            if !board.place_bomb(i) {
+                return Err("too many mines in a cell");
+            }
+        }
+    }
+    let bombs: u32 = board.cells().map(|cell| cell.contents.mines()).sum();
+    if bombs != config.num_bombs {
+        return Err("wrong number of mines");
+    }
+    Ok(board)
+}
+
+fn decode(file: SaveFile) -> Result<SavedGame, Error> {
+    let invalid = |e: &str| Error::Invalid(format!("saved game is invalid: {e}"));
+
+    if !(1..=VERSION).contains(&file.version) {
+        return Err(Error::Invalid(format!(
+            "saved game has version {}, expected {VERSION} or older",
+            file.version
+        )));
+    }
+
+    let code: GameCode = file.code.parse().map_err(|_| invalid("bad game code"))?;
+    let difficulty = match file.difficulty {
+        Some(name) => Some(Difficulty::from_name(&name).ok_or_else(|| invalid("bad difficulty"))?),
+        None => None,
+    };
+
+    if code.config.validate().is_err() {
+        return Err(invalid("board size doesn't match"));
+    }
+    let cells = read_rows(&code.config.topology(), &file.cells)
+        .ok_or_else(|| invalid("board size doesn't match"))?;
+    let mut board = board_from_mines(&code.config, &file.mines).map_err(invalid)?;
+    let moves = file
+        .moves
+        .iter()
+        .map(|m| m.to_move(&code.config))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid("bad move"))?;
+
+    for (i, ch) in cells {
+        board[i].state = match ch {
+            '#' => CellState::Hidden,
+            'o' => CellState::Revealed,
+            'F' => CellState::Flagged(1),
+            '2' | '3' => CellState::Flagged(ch.to_digit(10).unwrap()),
+            '?' => CellState::Questioned,
+            _ => return Err(invalid("unknown cell state")),
+        };
+    }
+
+    Ok(SavedGame {
+        game: Game::resume(
+            code.config,
+            code.seed,
+            board,
+            Duration::from_millis(file.elapsed_ms),
+            file.hints,
+            moves,
+            file.undos,
+        ),
+        difficulty,
+        fixed_seed: file.fixed_seed,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use mines::Config;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut game = Game::with_seed(Config::from(&Difficulty::Beginner), 3);
+        game.enable_practice();
+        game.reveal((4, 4));
+        let hidden = game
+            .board()
+            .cells()
+            .position(|cell| cell.state == CellState::Hidden)
+            .unwrap();
+        let flagged = (hidden / 9, hidden % 9);
+        game.toggle_flag(flagged);
+        game.hint();
+
+        let saved = SavedGame {
+            game,
+            difficulty: Some(Difficulty::Beginner),
+            fixed_seed: true,
+        };
+        let file = encode(&saved);
+        let elapsed = Duration::from_millis(file.elapsed_ms);
+        let loaded = parse(&toml::to_string(&file).unwrap()).unwrap();
+
+        assert_eq!(loaded.difficulty, Some(Difficulty::Beginner));
+        assert!(loaded.fixed_seed);
+        assert_eq!(loaded.game.code(), saved.game.code());
+        assert_eq!(loaded.game.hints(), saved.game.hints());
+        assert!(loaded.game.is_practice());
+        let moves =
+            |game: &Game| -> Vec<_> { game.moves().iter().map(|m| (m.action, m.index)).collect() };
+        assert_eq!(moves(&loaded.game), moves(&saved.game));
+        assert!(loaded.game.elapsed() >= elapsed);
+        for (a, b) in loaded.game.board().cells().zip(saved.game.board().cells()) {
+            assert_eq!(a.contents, b.contents);
+            assert_eq!(a.state, b.state);
+        }
+        assert_eq!(loaded.game.board()[flagged].state, CellState::Flagged(1));
+    }
+
+    #[test]
+    fn test_stacked_round_trip() {
+        let config = Config {
+            max_mines: 3,
+            num_bombs: 60,
+            ..Config::from(&Difficulty::Beginner)
+        };
+        let mut game = Game::with_seed(config, 5);
+        game.reveal((4, 4));
+        let hidden = game
+            .board()
+            .cells()
+            .position(|cell| cell.state == CellState::Hidden)
+            .unwrap();
+        let flagged = (hidden / 9, hidden % 9);
+        game.toggle_flag(flagged);
+        game.toggle_flag(flagged);
+
+        let saved = SavedGame {
+            game,
+            difficulty: None,
+            fixed_seed: true,
+        };
+        let file = encode(&saved);
+        assert!(file.mines.iter().any(|row| row.contains('3')));
+        let loaded = decode(file).unwrap();
+        for (a, b) in loaded.game.board().cells().zip(saved.game.board().cells()) {
+            assert_eq!(a.contents, b.contents);
+            assert_eq!(a.state, b.state);
+        }
+        assert_eq!(loaded.game.board()[flagged].state, CellState::Flagged(2));
+    }
+
+    #[test]
+    fn test_layered_round_trip() {
+        let config = Config {
+            layers: 3,
+            ..Config::custom((4, 5), 12)
+        };
+        let mut game = Game::with_seed(config, 8);
+        game.reveal(Pos::new(1, 2, 2));
+        game.toggle_flag(Pos::new(2, 3, 4));
+
+        let saved = SavedGame {
+            game,
+            difficulty: None,
+            fixed_seed: true,
+        };
+        let file = encode(&saved);
+        // A row of each layer on every line.
+        assert_eq!(file.mines.len(), 4);
+        assert_eq!(file.cells[3].len(), 17);
+        assert_eq!(file.cells[3].chars().nth(16), Some('F'));
+        let loaded = decode(file).unwrap();
+        assert_eq!(loaded.game.config().layers, 3);
+        assert_eq!(loaded.game.moves()[1].index, Pos::new(2, 3, 4));
+        for (a, b) in loaded.game.board().cells().zip(saved.game.board().cells()) {
+            assert_eq!(a.contents, b.contents);
+            assert_eq!(a.state, b.state);
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_saves() {
+        let saved = SavedGame {
+            game: Game::with_seed(Config::from(&Difficulty::Beginner), 3),
+            difficulty: None,
+            fixed_seed: false,
+        };
+
+        let mut file = encode(&saved);
+        assert!(file.mines.is_empty());
+        file.version = VERSION + 1;
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+
+        let mut file = encode(&saved);
+        file.version = 1;
+        assert!(decode(file).is_ok());
+
+        let mut file = encode(&saved);
+        file.cells.pop();
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+
+        let mut file = encode(&saved);
+        file.mines = vec![".".repeat(9); 9];
+        file.mines[0] = "*********".to_string();
+        assert!(matches!(decode(file), Err(Error::Invalid(_))));
+
+        assert!(matches!(parse("version = "), Err(Error::Corrupt(_))));
+    }
+}
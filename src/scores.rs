@@ -0,0 +1,178 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{self, Error};
+
+/// How many entries are kept for each category.
+const MAX_ENTRIES: usize = 10;
+
+const FILE_NAME: &str = "scores.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Score {
+    pub name: String,
+    pub millis: u64,
+    pub date: String,
+    /// Hints asked for during the game.
+    #[serde(default)]
+    pub hints: u32,
+}
+
+impl Score {
+    pub fn new(name: &str, time: Duration, hints: u32) -> Score {
+        Score {
+            name: name.to_string(),
+            millis: time.as_millis() as u64,
+            date: time::OffsetDateTime::now_utc().date().to_string(),
+            hints,
+        }
+    }
+
+    pub fn seconds(&self) -> f64 {
+        self.millis as f64 / 1000.0
+    }
+}
+
+/// The fastest wins, kept separately for each category (e.g. "Beginner") and
+/// sorted from fastest to slowest.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Scores {
+    categories: BTreeMap<String, Vec<Score>>,
+}
+
+impl Scores {
+    pub fn path() -> Result<PathBuf, Error> {
+        storage::data_file(FILE_NAME)
+    }
+
+    /// Reads the score file. A missing file is an empty table.
+    pub fn load() -> Result<Scores, Error> {
+        Scores::load_from(&Scores::path()?)
+    }
+
+    /// Like [`Scores::load`], but a corrupt file is moved aside to
+    /// `scores.toml.bak` and an empty table is returned in its place, so that
+    /// recording a new score never fails because of an old bad file.
+    pub fn load_or_reset() -> Result<Scores, Error> {
+        let path = Scores::path()?;
+        match Scores::load_from(&path) {
+            Err(Error::Corrupt(_)) => {
+                fs::rename(&path, path.with_extension("toml.bak"))?;
+                Ok(Scores::default())
+            }
+            res => res,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        storage::write(&Scores::path()?, self)
+    }
+
+    fn load_from(path: &Path) -> Result<Scores, Error> {
+        match storage::read(path)? {
+            Some(text) => Scores::parse(&text),
+            None => Ok(Scores::default()),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Scores, Error> {
+        let mut scores: Scores = toml::from_str(text).map_err(Error::Corrupt)?;
+        for list in scores.categories.values_mut() {
+            list.sort_by_key(|s| s.millis);
+            list.truncate(MAX_ENTRIES);
+        }
+        Ok(scores)
+    }
+
+    pub fn get(&self, category: &str) -> &[Score] {
+        self.categories
+            .get(category)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Whether a win in `time` would make it onto the table for `category`.
+    pub fn qualifies(&self, category: &str, time: Duration) -> bool {
+        let list = self.get(category);
+        list.len() < MAX_ENTRIES
+            || list
+                .last()
+                .is_some_and(|s| (time.as_millis() as u64) < s.millis)
+    }
+
+    /// Inserts `score` in order and returns its 1-based rank, or `None` if it
+    /// was too slow to be kept.
+    pub fn insert(&mut self, category: &str, score: Score) -> Option<usize> {
+        let list = self.categories.entry(category.to_string()).or_default();
+        let pos = list.partition_point(|s| s.millis <= score.millis);
+        if pos >= MAX_ENTRIES {
+            return None;
+        }
+        list.insert(pos, score);
+        list.truncate(MAX_ENTRIES);
+        Some(pos + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn score(name: &str, millis: u64) -> Score {
+        Score {
+            name: name.to_string(),
+            millis,
+            date: "2024-01-01".to_string(),
+            hints: 0,
+        }
+    }
+
+    #[test]
+    fn test_insert_keeps_fastest_sorted() {
+        let mut scores = Scores::default();
+        for i in 0..MAX_ENTRIES as u64 {
+            scores.insert("Beginner", score("a", 1000 * (i + 1)));
+        }
+        assert!(!scores.qualifies("Beginner", Duration::from_secs(20)));
+        assert!(scores.qualifies("Beginner", Duration::from_millis(500)));
+        assert!(scores.qualifies("Expert", Duration::from_secs(999)));
+
+        assert_eq!(scores.insert("Beginner", score("b", 20_000)), None);
+        assert_eq!(scores.insert("Beginner", score("c", 2500)), Some(3));
+
+        let list = scores.get("Beginner");
+        assert_eq!(list.len(), MAX_ENTRIES);
+        assert_eq!(list[2].name, "c");
+        assert!(list.windows(2).all(|w| w[0].millis <= w[1].millis));
+        assert!(scores.get("Expert").is_empty());
+    }
+
+    #[test]
+    fn test_round_trip_and_corrupt() {
+        let mut scores = Scores::default();
+        scores.insert("Expert", score("x", 90_000));
+        scores.insert("Beginner", score("y", 5_000));
+
+        let text = toml::to_string(&scores).unwrap();
+        let parsed = Scores::parse(&text).unwrap();
+        assert_eq!(parsed.get("Expert"), scores.get("Expert"));
+        assert_eq!(parsed.get("Beginner"), scores.get("Beginner"));
+
+        assert!(matches!(
+            Scores::parse("Beginner = 3"),
+            Err(Error::Corrupt(_))
+        ));
+        assert!(Scores::parse("").unwrap().get("Beginner").is_empty());
+
+        // Scores saved before hints were counted had none.
+        let old = "Beginner = [{ name = \"z\", millis = 9000, date = \"2024-01-01\" }]";
+        assert_eq!(Scores::parse(old).unwrap().get("Beginner")[0].hints, 0);
+    }
+}
@@ -0,0 +1,360 @@
+//! Preferences kept between sessions, in `settings.toml` in the config
+//! directory.
+
+use std::path::PathBuf;
+
+use cursive::event::{Event, Key, MouseButton, MouseEvent};
+use mines::{Difficulty, SafeStart};
+use serde::Serialize;
+use toml::Value;
+
+use crate::{
+    storage::{self, Error},
+    theme::{self, Builtin},
+};
+
+const FILE_NAME: &str = "settings.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub safe_start: SafeStart,
+    /// Let right click cycle through question marks.
+    pub question_marks: bool,
+    /// A built-in theme, or [`theme::CUSTOM`] for the user's theme file.
+    pub theme: String,
+    /// Columns per cell: 3 shows `[1]`, 2 shows `1 ` and 1 shows `1`.
+    pub cell_width: usize,
+    pub bindings: Bindings,
+    /// Selected in the start menu.
+    pub difficulty: Difficulty,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            safe_start: SafeStart::Area,
+            question_marks: false,
+            theme: Builtin::Classic.name().to_string(),
+            cell_width: 3,
+            bindings: Bindings::default(),
+            difficulty: Difficulty::Beginner,
+        }
+    }
+}
+
+/// Which letters move the cursor, besides the arrow keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keys {
+    All,
+    Vi,
+    Wasd,
+    Arrows,
+}
+
+impl Keys {
+    pub const ALL: [Keys; 4] = [Keys::All, Keys::Vi, Keys::Wasd, Keys::Arrows];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Keys::All => "all",
+            Keys::Vi => "vi",
+            Keys::Wasd => "wasd",
+            Keys::Arrows => "arrows",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Keys> {
+        Keys::ALL.into_iter().find(|k| k.name() == name)
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Keys::All => "Arrows, hjkl and wasd",
+            Keys::Vi => "Arrows and hjkl",
+            Keys::Wasd => "Arrows and wasd",
+            Keys::Arrows => "Arrows only",
+        }
+    }
+
+    /// The arrow key that `c` stands for.
+    fn arrow(&self, c: char) -> Option<Key> {
+        let vi = matches!(self, Keys::All | Keys::Vi);
+        let wasd = matches!(self, Keys::All | Keys::Wasd);
+        match c {
+            'k' if vi => Some(Key::Up),
+            'j' if vi => Some(Key::Down),
+            'h' if vi => Some(Key::Left),
+            'l' if vi => Some(Key::Right),
+            'w' if wasd => Some(Key::Up),
+            's' if wasd => Some(Key::Down),
+            'a' if wasd => Some(Key::Left),
+            'd' if wasd => Some(Key::Right),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bindings {
+    pub keys: Keys,
+    /// Left click flags and right click reveals.
+    pub swap_buttons: bool,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            keys: Keys::All,
+            swap_buttons: false,
+        }
+    }
+}
+
+impl Bindings {
+    /// Turns movement letters into arrow keys and swaps the mouse buttons if
+    /// asked to, so the board only has to handle the usual events.
+    pub fn translate(&self, e: Event) -> Event {
+        let swap = |button| match button {
+            MouseButton::Left if self.swap_buttons => MouseButton::Right,
+            MouseButton::Right if self.swap_buttons => MouseButton::Left,
+            button => button,
+        };
+        match e {
+            Event::Char(c) => self.keys.arrow(c).map_or(e, Event::Key),
+            Event::Mouse {
+                offset,
+                position,
+                event,
+            } => Event::Mouse {
+                offset,
+                position,
+                event: match event {
+                    MouseEvent::Press(button) => MouseEvent::Press(swap(button)),
+                    MouseEvent::Release(button) => MouseEvent::Release(swap(button)),
+                    event => event,
+                },
+            },
+            e => e,
+        }
+    }
+
+    /// Checks and applies one entry of the `[bindings]` table.
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "keys" => {
+                self.keys = value
+                    .as_str()
+                    .and_then(Keys::from_name)
+                    .ok_or("expected all, vi, wasd or arrows")?
+            }
+            "swap_buttons" => {
+                self.swap_buttons = value.as_bool().ok_or("expected true or false")?
+            }
+            _ => return Err("unknown setting".to_string()),
+        }
+        Ok(())
+    }
+}
+
+impl Settings {
+    /// Checks and applies one top-level entry of the file.
+    fn set(&mut self, key: &str, value: &Value) -> Result<(), String> {
+        match key {
+            "safe_start" => {
+                self.safe_start = match value.as_str() {
+                    Some("area") => SafeStart::Area,
+                    Some("cell") => SafeStart::Cell,
+                    _ => return Err("expected area or cell".to_string()),
+                }
+            }
+            "question_marks" => {
+                self.question_marks = value.as_bool().ok_or("expected true or false")?
+            }
+            "theme" => {
+                self.theme = value
+                    .as_str()
+                    .filter(|&name| Builtin::from_name(name).is_some() || name == theme::CUSTOM)
+                    .ok_or("expected classic, high-contrast, colorblind, mono or custom")?
+                    .to_string()
+            }
+            "cell_width" => {
+                self.cell_width = value
+                    .as_integer()
+                    .filter(|w| (1..=3).contains(w))
+                    .ok_or("expected 1, 2 or 3")? as usize
+            }
+            "difficulty" => {
+                self.difficulty = value
+                    .as_str()
+                    .and_then(Difficulty::from_name)
+                    .ok_or("expected Beginner, Intermediate or Expert")?
+            }
+            "bindings" => return Err("expected a table".to_string()),
+            _ => return Err("unknown setting".to_string()),
+        }
+        Ok(())
+    }
+}
+
+/// The on-disk form of [`Settings`].
+#[derive(Debug, Serialize)]
+struct SettingsFile {
+    safe_start: &'static str,
+    question_marks: bool,
+    theme: String,
+    cell_width: usize,
+    difficulty: &'static str,
+    bindings: BindingsFile,
+}
+
+#[derive(Debug, Serialize)]
+struct BindingsFile {
+    keys: &'static str,
+    swap_buttons: bool,
+}
+
+pub fn path() -> Result<PathBuf, Error> {
+    storage::config_file(FILE_NAME)
+}
+
+/// Reads the settings file. A missing file gives the defaults. Entries that
+/// aren't known or don't make sense keep their defaults, and are described
+/// in the list returned alongside.
+pub fn load() -> Result<(Settings, Vec<String>), Error> {
+    match storage::read(&path()?)? {
+        Some(text) => parse(&text),
+        None => Ok((Settings::default(), Vec::new())),
+    }
+}
+
+pub fn save(settings: &Settings) -> Result<(), Error> {
+    storage::write(&path()?, &encode(settings))
+}
+
+fn parse(text: &str) -> Result<(Settings, Vec<String>), Error> {
+    let table: toml::Table = toml::from_str(text).map_err(Error::Corrupt)?;
+    let mut settings = Settings::default();
+    let mut problems = Vec::new();
+
+    for (key, value) in &table {
+        match (key.as_str(), value) {
+            ("bindings", Value::Table(bindings)) => {
+                for (key, value) in bindings {
+                    if let Err(e) = settings.bindings.set(key, value) {
+                        problems.push(format!("bindings.{key}: {e}"));
+                    }
+                }
+            }
+            _ => {
+                if let Err(e) = settings.set(key, value) {
+                    problems.push(format!("{key}: {e}"));
+                }
+            }
+        }
+    }
+    Ok((settings, problems))
+}
+
+fn encode(settings: &Settings) -> SettingsFile {
+    SettingsFile {
+        safe_start: match settings.safe_start {
+            SafeStart::Area => "area",
+            SafeStart::Cell => "cell",
+        },
+        question_marks: settings.question_marks,
+        theme: settings.theme.clone(),
+        cell_width: settings.cell_width,
+        difficulty: settings.difficulty.name(),
+        bindings: BindingsFile {
+            keys: settings.bindings.keys.name(),
+            swap_buttons: settings.bindings.swap_buttons,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cursive::Vec2;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let settings = Settings {
+            safe_start: SafeStart::Cell,
+            question_marks: true,
+            theme: theme::CUSTOM.to_string(),
+            cell_width: 2,
+            bindings: Bindings {
+                keys: Keys::Vi,
+                swap_buttons: true,
+            },
+            difficulty: Difficulty::Expert,
+        };
+        let text = toml::to_string(&encode(&settings)).unwrap();
+        assert_eq!(parse(&text).unwrap(), (settings, Vec::new()));
+    }
+
+    #[test]
+    fn test_reports_problems() {
+        let text = r#"
+            question_marks = true
+            cell_width = 4
+            colour = "red"
+            difficulty = "Insane"
+            [bindings]
+            keys = "vi"
+            swap = true
+        "#;
+        let (settings, mut problems) = parse(text).unwrap();
+        assert!(settings.question_marks);
+        assert_eq!(settings.cell_width, 3);
+        assert_eq!(settings.bindings.keys, Keys::Vi);
+        assert_eq!(settings.difficulty, Difficulty::Beginner);
+
+        problems.sort();
+        assert_eq!(
+            problems,
+            [
+                "bindings.swap: unknown setting",
+                "cell_width: expected 1, 2 or 3",
+                "colour: unknown setting",
+                "difficulty: expected Beginner, Intermediate or Expert",
+            ]
+        );
+
+        assert!(matches!(parse("theme ="), Err(Error::Corrupt(_))));
+        assert_eq!(
+            parse("bindings = 1").unwrap().1,
+            ["bindings: expected a table"]
+        );
+    }
+
+    #[test]
+    fn test_translate() {
+        let vi = Bindings {
+            keys: Keys::Vi,
+            swap_buttons: true,
+        };
+        assert_eq!(vi.translate(Event::Char('h')), Event::Key(Key::Left));
+        assert_eq!(vi.translate(Event::Char('a')), Event::Char('a'));
+        assert_eq!(
+            Bindings::default().translate(Event::Char('a')),
+            Event::Key(Key::Left)
+        );
+
+        let click = |button| Event::Mouse {
+            offset: Vec2::zero(),
+            position: Vec2::new(1, 1),
+            event: MouseEvent::Press(button),
+        };
+        assert_eq!(
+            vi.translate(click(MouseButton::Left)),
+            click(MouseButton::Right)
+        );
+        assert_eq!(
+            Bindings::default().translate(click(MouseButton::Left)),
+            click(MouseButton::Left)
+        );
+    }
+}
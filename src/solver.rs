@@ -0,0 +1,399 @@
+//! Logical deductions from what the player can see: revealed hints, flags and
+//! the total number of mines. Flags are trusted to be on mines, with as many
+//! as they say; question marks count as hidden. Where that can't be true,
+//! because a hint or the mine count is short of the flags, nothing follows.
+
+use crate::{
+    board::{Board, CellContents, CellState},
+    topology::Pos,
+};
+
+/// Cells that are certainly safe, and cells certain to hold a known number
+/// of mines.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Deductions {
+    pub safe: Vec<Pos>,
+    pub mines: Vec<(Pos, u32)>,
+}
+
+/// A single move the visible board proves correct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hint {
+    Safe(Pos),
+    /// The cell holds this many mines.
+    Mine(Pos, u32),
+}
+
+impl Deductions {
+    pub fn is_empty(&self) -> bool {
+        self.safe.is_empty() && self.mines.is_empty()
+    }
+
+    /// One of the deductions, preferring a safe cell to reveal over a mine to
+    /// flag.
+    pub fn first(&self) -> Option<Hint> {
+        self.safe
+            .first()
+            .map(|&i| Hint::Safe(i))
+            .or_else(|| self.mines.first().map(|&(i, n)| Hint::Mine(i, n)))
+    }
+
+    fn add_safe(&mut self, cells: &[Pos]) {
+        for &i in cells {
+            if !self.safe.contains(&i) {
+                self.safe.push(i);
+            }
+        }
+    }
+
+    fn add_mines(&mut self, cells: &[Pos], n: usize) {
+        for &i in cells {
+            if !self.mines.iter().any(|&(j, _)| j == i) {
+                self.mines.push((i, n as u32));
+            }
+        }
+    }
+
+    /// Records whatever follows from `cells` holding `mines` between them,
+    /// with at most `cap` in any one cell: nothing if none of them are
+    /// mines, `cap` each if they're all full, and the lot if there is only
+    /// one cell.
+    fn settle(&mut self, cells: &[Pos], mines: usize, cap: usize) {
+        if mines == 0 {
+            self.add_safe(cells);
+        } else if mines == cells.len() * cap {
+            self.add_mines(cells, cap);
+        } else if cells.len() == 1 && mines < cap {
+            self.add_mines(cells, mines);
+        }
+    }
+}
+
+/// `cells` hold exactly `mines` mines between them. `cells` is sorted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Constraint {
+    pub(crate) cells: Vec<Pos>,
+    pub(crate) mines: usize,
+}
+
+impl Constraint {
+    fn is_subset(&self, other: &Constraint) -> bool {
+        self.cells.len() <= other.cells.len()
+            && self
+                .cells
+                .iter()
+                .all(|i| other.cells.binary_search(i).is_ok())
+    }
+
+    fn overlaps(&self, other: &Constraint) -> bool {
+        self.cells
+            .iter()
+            .any(|i| other.cells.binary_search(i).is_ok())
+    }
+
+    /// The cells of `self` that aren't in `other`.
+    fn minus(&self, other: &Constraint) -> Vec<Pos> {
+        self.cells
+            .iter()
+            .filter(|i| other.cells.binary_search(i).is_err())
+            .copied()
+            .collect()
+    }
+}
+
+/// One constraint per revealed hint that still borders unknown cells, or
+/// `None` if a hint has more flags around it than it counts.
+pub(crate) fn hint_constraints(board: &Board) -> Option<Vec<Constraint>> {
+    let mut res = Vec::new();
+    for index in board.indices() {
+        let cell = &board[index];
+        let (CellState::Revealed, CellContents::Hint(n)) = (&cell.state, &cell.contents) else {
+            continue;
+        };
+
+        let mut cells = Vec::new();
+        let mut flagged = 0;
+        for i in board.neighbors(index) {
+            match board[i].state {
+                CellState::Hidden | CellState::Questioned => cells.push(i),
+                CellState::Flagged(n) => flagged += n as usize,
+                CellState::Revealed => (),
+            }
+        }
+        let mines = (*n as usize).checked_sub(flagged)?;
+        if !cells.is_empty() {
+            cells.sort();
+            res.push(Constraint { cells, mines });
+        }
+    }
+    Some(res)
+}
+
+/// [`hint_constraints`], plus one for the mine count over every unknown cell.
+/// `None` if the flags can't all be on mines.
+fn constraints(board: &Board, num_bombs: u32) -> Option<Vec<Constraint>> {
+    let mut res = hint_constraints(board)?;
+
+    let mut unknown = Vec::new();
+    let mut flags = 0;
+    for i in board.indices() {
+        match board[i].state {
+            CellState::Hidden | CellState::Questioned => unknown.push(i),
+            CellState::Flagged(n) => flags += n as usize,
+            CellState::Revealed => (),
+        }
+    }
+    let mines = (num_bombs as usize).checked_sub(flags)?;
+    if !unknown.is_empty() {
+        res.push(Constraint {
+            cells: unknown,
+            mines,
+        });
+    }
+    res.sort_by(|a, b| a.cells.cmp(&b.cells));
+    res.dedup();
+    Some(res)
+}
+
+/// Whether the flags could all be on mines: no hint has more of them around
+/// it than it counts, and there aren't more than `num_bombs` in all.
+pub fn flags_add_up(board: &Board, num_bombs: u32) -> bool {
+    constraints(board, num_bombs).is_some()
+}
+
+/// Finds cells that are certainly safe or certainly mines on a board with
+/// `num_bombs` mines in total. Tries single hints on their own first, then
+/// pairs of hints where one's cells are a subset of the other's, then any two
+/// overlapping hints. Returns as soon as a rule finds anything, so the result
+/// isn't necessarily complete, and nothing if the flags don't add up.
+pub fn deduce(board: &Board, num_bombs: u32) -> Deductions {
+    let Some(constraints) = constraints(board, num_bombs) else {
+        return Deductions::default();
+    };
+    let cap = board.max_mines() as usize;
+    let mut found = Deductions::default();
+
+    for k in &constraints {
+        found.settle(&k.cells, k.mines, cap);
+    }
+    if !found.is_empty() {
+        return found;
+    }
+
+    for a in &constraints {
+        for b in &constraints {
+            if a == b || !a.is_subset(b) || b.mines < a.mines {
+                continue;
+            }
+            found.settle(&b.minus(a), b.mines - a.mines, cap);
+        }
+    }
+    if !found.is_empty() {
+        return found;
+    }
+
+    // Two overlapping hints bound how many mines their shared cells hold,
+    // which bounds the mines in the cells only one of them sees.
+    for a in &constraints {
+        for b in &constraints {
+            if a == b || !a.overlaps(b) {
+                continue;
+            }
+            let only_a = a.minus(b);
+            let only_b = b.minus(a);
+            let shared = a.cells.len() - only_a.len();
+            let least = a
+                .mines
+                .saturating_sub(only_a.len() * cap)
+                .max(b.mines.saturating_sub(only_b.len() * cap));
+            let most = (shared * cap).min(a.mines).min(b.mines);
+            if only_a.is_empty() || least > most {
+                continue;
+            }
+            if a.mines == least {
+                found.add_safe(&only_a);
+            } else if a.mines - most == only_a.len() * cap {
+                found.add_mines(&only_a, cap);
+            } else if least == most {
+                found.settle(&only_a, a.mines - most, cap);
+            }
+        }
+    }
+    found
+}
+
+/// One provable move on the visible board, if there is one.
+pub fn hint(board: &Board, num_bombs: u32) -> Option<Hint> {
+    deduce(board, num_bombs).first()
+}
+
+/// Whether `board`, with its bombs placed and every cell hidden, can be
+/// cleared from a first reveal at `first` without ever having to guess.
+pub fn is_solvable(board: &Board, num_bombs: u32, first: impl Into<Pos>) -> bool {
+    let first = first.into();
+    let mut board = board.clone();
+    if board[first].contents.is_bomb() {
+        return false;
+    }
+    board.reveal(first);
+
+    while !board.is_cleared() {
+        let found = deduce(&board, num_bombs);
+        if found.is_empty() {
+            return false;
+        }
+        for (i, n) in found.mines {
+            board[i].state = CellState::Flagged(n);
+        }
+        for i in found.safe {
+            debug_assert!(!board[i].contents.is_bomb());
+            board.reveal(i);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::topology::Topology;
+
+    #[test]
+    fn test_single_point() {
+        let mut board = Board::new((3, 3));
+        board.place_bomb((0, 2));
+        for i in [(0, 0), (1, 0), (1, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+            board[i].state = CellState::Revealed;
+        }
+        // The 0 in the corner clears its last hidden neighbor...
+        let found = deduce(&board, 1);
+        assert_eq!(found.safe, vec![(0, 1).into()]);
+        assert!(found.mines.is_empty());
+
+        // ...whose 1 then has a single hidden neighbor.
+        board[(0, 1)].state = CellState::Revealed;
+        let found = deduce(&board, 1);
+        assert_eq!(found.mines, vec![((0, 2).into(), 1)]);
+    }
+
+    #[test]
+    fn test_subset() {
+        // Hints 1 2 1 along the top of a 2x3 board with the bottom row hidden:
+        // the mines are in the corners.
+        let mut board = Board::new((2, 3));
+        board.place_bomb((1, 0));
+        board.place_bomb((1, 2));
+        for c in 0..3 {
+            board[(0, c)].state = CellState::Revealed;
+        }
+        let mut found = deduce(&board, 2);
+        found.mines.sort();
+        assert_eq!(found.mines, vec![((1, 0).into(), 1), ((1, 2).into(), 1)]);
+
+        for (i, n) in found.mines {
+            board[i].state = CellState::Flagged(n);
+        }
+        assert_eq!(deduce(&board, 2).safe, vec![(1, 1).into()]);
+    }
+
+    #[test]
+    fn test_overlap() {
+        // The top row shows # 1 1 2 2 #, with everything below it hidden. No
+        // hint's cells contain another's, but the second 1 and the first 2
+        // share two cells that hold at most one mine: so the 2's last cell is
+        // a mine, and the 1's first cell is safe.
+        let mut board = Board::new((3, 6));
+        for i in [(0, 0), (1, 3), (1, 4)] {
+            board.place_bomb(i);
+        }
+        for c in 1..5 {
+            board[(0, c)].state = CellState::Revealed;
+        }
+
+        let found = deduce(&board, 3);
+        assert!(found.mines.contains(&((1, 4).into(), 1)));
+        assert!(found.safe.contains(&(1, 1).into()));
+    }
+
+    #[test]
+    fn test_hint() {
+        let mut board = Board::new((1, 3));
+        board.place_bomb((0, 2));
+        board[(0, 0)].state = CellState::Revealed;
+        assert_eq!(hint(&board, 1), Some(Hint::Safe((0, 1).into())));
+
+        board[(0, 1)].state = CellState::Revealed;
+        assert_eq!(hint(&board, 1), Some(Hint::Mine((0, 2).into(), 1)));
+
+        let mut board = Board::new((2, 2));
+        board.place_bomb((1, 1));
+        board[(0, 0)].state = CellState::Revealed;
+        assert_eq!(hint(&board, 1), None);
+    }
+
+    #[test]
+    fn test_misplaced_flag() {
+        // The 1 has two flags next to it, so one of them is wrong. Trusting
+        // them would clear the mine under it.
+        let mut board = Board::new((2, 5));
+        board.place_bomb((1, 1));
+        board.place_bomb((1, 4));
+        board[(0, 1)].state = CellState::Revealed;
+        board[(0, 0)].state = CellState::Flagged(1);
+        board[(0, 2)].state = CellState::Flagged(1);
+        assert!(!flags_add_up(&board, 3));
+        assert!(deduce(&board, 3).is_empty());
+        assert_eq!(hint(&board, 3), None);
+
+        // Moving one of them puts two flags on a board with one mine.
+        board[(0, 2)].state = CellState::Hidden;
+        board[(0, 4)].state = CellState::Flagged(1);
+        assert!(!flags_add_up(&board, 1));
+        assert!(deduce(&board, 1).is_empty());
+        assert!(flags_add_up(&board, 3));
+    }
+
+    #[test]
+    fn test_stacked_mines() {
+        // # 4 # along a single row, with cells holding up to three mines:
+        // the 4 can only be 3 + 1 or 2 + 2 or 1 + 3, so nothing is certain.
+        let mut board = Board::with_max_mines(Topology::rect((1, 3)), 3);
+        board.place_bomb((0, 0));
+        for _ in 0..3 {
+            board.place_bomb((0, 2));
+        }
+        board[(0, 1)].state = CellState::Revealed;
+        assert_eq!(board[(0, 1)].contents, CellContents::Hint(4));
+        assert!(deduce(&board, 4).is_empty());
+
+        // Once the left one is flagged with its single mine, the right one
+        // must hold the other three.
+        board[(0, 0)].state = CellState::Flagged(1);
+        assert_eq!(hint(&board, 4), Some(Hint::Mine((0, 2).into(), 3)));
+
+        // A 6 there would need both neighbors full.
+        let mut board = Board::with_max_mines(Topology::rect((1, 3)), 3);
+        for _ in 0..3 {
+            board.place_bomb((0, 0));
+            board.place_bomb((0, 2));
+        }
+        board[(0, 1)].state = CellState::Revealed;
+        let mut found = deduce(&board, 6);
+        found.mines.sort();
+        assert_eq!(found.mines, vec![((0, 0).into(), 3), ((0, 2).into(), 3)]);
+        assert!(is_solvable(&board, 6, (0, 1)));
+    }
+
+    #[test]
+    fn test_is_solvable() {
+        let mut board = Board::new((1, 5));
+        board.place_bomb((0, 4));
+        assert!(is_solvable(&board, 1, (0, 0)));
+        assert!(!is_solvable(&board, 1, (0, 4)));
+
+        // The 1 could be on any of its three hidden neighbors.
+        let mut board = Board::new((2, 2));
+        board.place_bomb((1, 1));
+        assert!(!is_solvable(&board, 1, (0, 0)));
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+#[derive(Debug)]
+pub enum Error {
+    NoDataDir,
+    NoConfigDir,
+    Io(io::Error),
+    Corrupt(toml::de::Error),
+    Serialize(toml::ser::Error),
+    /// The file parsed but its contents don't make sense.
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoDataDir => write!(f, "could not determine a data directory"),
+            Error::NoConfigDir => write!(f, "could not determine a config directory"),
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Corrupt(e) => write!(f, "file is corrupt: {}", e.message()),
+            Error::Serialize(e) => write!(f, "could not encode data: {e}"),
+            Error::Invalid(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::Io(value)
+    }
+}
+
+/// Where a file called `name` lives in the user's data directory.
+pub fn data_file(name: &str) -> Result<PathBuf, Error> {
+    directories::ProjectDirs::from("", "", "mines")
+        .map(|dirs| dirs.data_dir().join(name))
+        .ok_or(Error::NoDataDir)
+}
+
+/// Where a file called `name` lives in the user's config directory.
+pub fn config_file(name: &str) -> Result<PathBuf, Error> {
+    directories::ProjectDirs::from("", "", "mines")
+        .map(|dirs| dirs.config_dir().join(name))
+        .ok_or(Error::NoConfigDir)
+}
+
+/// Reads a file, or `None` if it doesn't exist.
+pub fn read(path: &Path) -> Result<Option<String>, Error> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(Some(text)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Writes `value` as TOML, creating the parent directory if needed.
+pub fn write<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let text = toml::to_string(value).map_err(Error::Serialize)?;
+    fs::write(path, text)?;
+    Ok(())
+}
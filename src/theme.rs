@@ -0,0 +1,426 @@
+//! Colors for the board: a few built-in themes, and theme files that adjust
+//! one of them.
+//!
+//! A theme file names the built-in theme it starts from and overrides the
+//! colors of some parts of the board:
+//!
+//! ```toml
+//! base = "classic"
+//!
+//! [colors]
+//! 1 = { fg = "#0000ff" }
+//! exploded = { fg = "white", bg = "red" }
+//!
+//! # Applied on top of `colors` on terminals with only 8 colors.
+//! [basic]
+//! 1 = { fg = "blue" }
+//! ```
+//!
+//! Colors are anything cursive understands: `"red"`, `"light blue"`,
+//! `"#rrggbb"`, or a palette color such as `"view"`.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use cursive::theme::{ColorStyle, ColorType};
+use serde::Deserialize;
+
+use crate::storage::{self, Error};
+
+/// The user's own theme, picked with the name [`CUSTOM`].
+const FILE_NAME: &str = "theme.toml";
+
+/// Names the theme file in the config directory rather than a built-in
+/// theme.
+pub const CUSTOM: &str = "custom";
+
+/// The parts of the board a theme colors: the hints 0 to 8, then the rest.
+const PARTS: [&str; 15] = [
+    "0",
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "7",
+    "8",
+    "hidden",
+    "flag",
+    "question",
+    "mine",
+    "exploded",
+    "wrong_flag",
+];
+const HIDDEN: usize = 9;
+const FLAG: usize = 10;
+const QUESTION: usize = 11;
+const MINE: usize = 12;
+const EXPLODED: usize = 13;
+const WRONG_FLAG: usize = 14;
+
+/// A foreground and background color for each of [`PARTS`]. An empty name
+/// keeps the color of the view around the board.
+type Palette = [(&'static str, &'static str); PARTS.len()];
+
+const CLASSIC: Palette = [
+    ("", ""),
+    ("#0000ff", ""),
+    ("#008000", ""),
+    ("#ff0000", ""),
+    ("#000080", ""),
+    ("#800000", ""),
+    ("#008080", ""),
+    ("#000000", ""),
+    ("#808080", ""),
+    ("#000000", "#c0c0c0"),
+    ("#ff0000", "#c0c0c0"),
+    ("#000000", "#c0c0c0"),
+    ("#000000", ""),
+    ("#000000", "#ff0000"),
+    ("#ffffff", "#800000"),
+];
+
+const CLASSIC_BASIC: Palette = [
+    ("", ""),
+    ("blue", ""),
+    ("green", ""),
+    ("red", ""),
+    ("magenta", ""),
+    ("red", ""),
+    ("cyan", ""),
+    ("black", ""),
+    ("black", ""),
+    ("black", "cyan"),
+    ("red", "cyan"),
+    ("black", "cyan"),
+    ("black", ""),
+    ("white", "red"),
+    ("white", "magenta"),
+];
+
+const HIGH_CONTRAST: Palette = [
+    ("light white", "black"),
+    ("light cyan", "black"),
+    ("light green", "black"),
+    ("light red", "black"),
+    ("light yellow", "black"),
+    ("light magenta", "black"),
+    ("light blue", "black"),
+    ("light white", "black"),
+    ("light white", "black"),
+    ("black", "light white"),
+    ("black", "light yellow"),
+    ("black", "light cyan"),
+    ("light white", "black"),
+    ("light white", "red"),
+    ("light white", "magenta"),
+];
+
+/// The Okabe-Ito colors, which stay apart with any kind of color blindness.
+const COLORBLIND: Palette = [
+    ("", ""),
+    ("#0072b2", ""),
+    ("#009e73", ""),
+    ("#d55e00", ""),
+    ("#cc79a7", ""),
+    ("#e69f00", ""),
+    ("#56b4e9", ""),
+    ("#000000", ""),
+    ("#808080", ""),
+    ("#000000", "#c0c0c0"),
+    ("#d55e00", "#c0c0c0"),
+    ("#0072b2", "#c0c0c0"),
+    ("#000000", ""),
+    ("#ffffff", "#d55e00"),
+    ("#ffffff", "#0072b2"),
+];
+
+const COLORBLIND_BASIC: Palette = [
+    ("", ""),
+    ("blue", ""),
+    ("cyan", ""),
+    ("red", ""),
+    ("magenta", ""),
+    ("yellow", ""),
+    ("blue", ""),
+    ("black", ""),
+    ("black", ""),
+    ("black", "white"),
+    ("red", "white"),
+    ("blue", "white"),
+    ("black", ""),
+    ("white", "red"),
+    ("white", "blue"),
+];
+
+const MONO: Palette = [
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("", ""),
+    ("view", "primary"),
+    ("view", "primary"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Builtin {
+    Classic,
+    HighContrast,
+    Colorblind,
+    Mono,
+}
+
+impl Builtin {
+    pub const ALL: [Builtin; 4] = [
+        Builtin::Classic,
+        Builtin::HighContrast,
+        Builtin::Colorblind,
+        Builtin::Mono,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::Classic => "classic",
+            Builtin::HighContrast => "high-contrast",
+            Builtin::Colorblind => "colorblind",
+            Builtin::Mono => "mono",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        Builtin::ALL.into_iter().find(|b| b.name() == name)
+    }
+
+    fn palette(&self, basic: bool) -> &'static Palette {
+        match (self, basic) {
+            (Builtin::Classic, false) => &CLASSIC,
+            (Builtin::Classic, true) => &CLASSIC_BASIC,
+            (Builtin::HighContrast, _) => &HIGH_CONTRAST,
+            (Builtin::Colorblind, false) => &COLORBLIND,
+            (Builtin::Colorblind, true) => &COLORBLIND_BASIC,
+            (Builtin::Mono, _) => &MONO,
+        }
+    }
+}
+
+/// The on-disk form of a theme.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    /// The built-in theme to start from; classic if not given.
+    base: Option<Builtin>,
+    #[serde(default)]
+    colors: BTreeMap<String, StyleFile>,
+    #[serde(default)]
+    basic: BTreeMap<String, StyleFile>,
+}
+
+/// Colors for one part. Those not given are kept from the base theme.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleFile {
+    fg: Option<String>,
+    bg: Option<String>,
+}
+
+/// How each part of the board is drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    styles: [ColorStyle; PARTS.len()],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::builtin(Builtin::Classic, false)
+    }
+}
+
+impl Theme {
+    /// A built-in theme, in its 8-color version if `basic` is set.
+    pub fn builtin(base: Builtin, basic: bool) -> Theme {
+        let file = ThemeFile {
+            base: Some(base),
+            ..Default::default()
+        };
+        Theme::from_file(&file, basic).expect("built-in themes are valid")
+    }
+
+    /// Hints past 8, which come up where cells hold several mines or on
+    /// layered boards, share 8's colors.
+    pub fn number(&self, n: u32) -> ColorStyle {
+        self.styles[n.min(8) as usize]
+    }
+
+    pub fn hidden(&self) -> ColorStyle {
+        self.styles[HIDDEN]
+    }
+
+    pub fn flag(&self) -> ColorStyle {
+        self.styles[FLAG]
+    }
+
+    pub fn question(&self) -> ColorStyle {
+        self.styles[QUESTION]
+    }
+
+    pub fn mine(&self) -> ColorStyle {
+        self.styles[MINE]
+    }
+
+    /// The mine that lost the game.
+    pub fn exploded(&self) -> ColorStyle {
+        self.styles[EXPLODED]
+    }
+
+    /// A flag that turned out not to be on a mine.
+    pub fn wrong_flag(&self) -> ColorStyle {
+        self.styles[WRONG_FLAG]
+    }
+
+    /// Applies `file` to its base theme. The `basic` colors are checked either
+    /// way, but only used if `basic` is set.
+    fn from_file(file: &ThemeFile, basic: bool) -> Result<Theme, Error> {
+        let base = file.base.unwrap_or(Builtin::Classic);
+        let mut styles = [ColorStyle::inherit_parent(); PARTS.len()];
+        for (style, &(fg, bg)) in styles.iter_mut().zip(base.palette(basic)) {
+            *style = ColorStyle::new(color(fg)?, color(bg)?);
+        }
+
+        for (table, apply) in [(&file.colors, true), (&file.basic, basic)] {
+            for (part, style) in table {
+                let i = PARTS
+                    .iter()
+                    .position(|p| p == part)
+                    .ok_or_else(|| Error::Invalid(format!("theme has no part called {part}")))?;
+                let fg = style.fg.as_deref().map(color).transpose()?;
+                let bg = style.bg.as_deref().map(color).transpose()?;
+                if apply {
+                    styles[i].front = fg.unwrap_or(styles[i].front);
+                    styles[i].back = bg.unwrap_or(styles[i].back);
+                }
+            }
+        }
+        Ok(Theme { styles })
+    }
+}
+
+fn color(name: &str) -> Result<ColorType, Error> {
+    if name.is_empty() {
+        return Ok(ColorType::InheritParent);
+    }
+    name.parse()
+        .map_err(|_| Error::Invalid(format!("theme has an unknown color: {name}")))
+}
+
+/// Whether the terminal likely shows more than the 8 basic colors. On those
+/// that don't, curses rounds each channel of an RGB color, which would turn
+/// most of the classic colors black.
+fn rich_colors() -> bool {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    matches!(var("COLORTERM").as_str(), "truecolor" | "24bit") || var("TERM").contains("256color")
+}
+
+pub fn path() -> Result<PathBuf, Error> {
+    storage::config_file(FILE_NAME)
+}
+
+/// The theme called `name`: a built-in one, the user's theme file for
+/// [`CUSTOM`], or else the theme file at that path.
+pub fn load(name: &str) -> Result<Theme, Error> {
+    if let Some(base) = Builtin::from_name(name) {
+        return Ok(Theme::builtin(base, !rich_colors()));
+    }
+    let path = match name {
+        CUSTOM => path()?,
+        name => PathBuf::from(name),
+    };
+    match storage::read(&path)? {
+        Some(text) => parse(&text, !rich_colors()),
+        None => Err(Error::Invalid(format!(
+            "there is no theme file at {}",
+            path.display()
+        ))),
+    }
+}
+
+fn parse(text: &str, basic: bool) -> Result<Theme, Error> {
+    Theme::from_file(&toml::from_str(text).map_err(Error::Corrupt)?, basic)
+}
+
+#[cfg(test)]
+mod test {
+    use cursive::theme::{BaseColor, Color};
+
+    use super::*;
+
+    #[test]
+    fn test_builtins() {
+        for base in Builtin::ALL {
+            assert_eq!(Builtin::from_name(base.name()), Some(base));
+            for basic in [false, true] {
+                let theme = Theme::builtin(base, basic);
+                assert_ne!(theme.exploded(), theme.mine());
+            }
+        }
+        let classic = Theme::builtin(Builtin::Classic, true);
+        assert_eq!(
+            classic.number(1).front,
+            ColorType::Color(Color::Dark(BaseColor::Blue))
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r##"
+            base = "mono"
+            [colors]
+            1 = { fg = "#0000ff" }
+            exploded = { bg = "red" }
+            [basic]
+            1 = { fg = "blue" }
+        "##;
+        let theme = parse(text, false).unwrap();
+        assert_eq!(
+            theme.number(1).front,
+            ColorType::Color(Color::Rgb(0, 0, 255))
+        );
+        assert_eq!(
+            theme.exploded().back,
+            ColorType::Color(Color::Dark(BaseColor::Red))
+        );
+        assert_eq!(
+            theme.number(2),
+            Theme::builtin(Builtin::Mono, false).number(2)
+        );
+
+        let theme = parse(text, true).unwrap();
+        assert_eq!(
+            theme.number(1).front,
+            ColorType::Color(Color::Dark(BaseColor::Blue))
+        );
+
+        assert!(matches!(
+            parse("[basic]\n9 = { fg = \"red\" }", false),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("[colors]\nflag = { fg = \"reddish\" }", false),
+            Err(Error::Invalid(_))
+        ));
+        assert!(matches!(
+            parse("base = \"neon\"", false),
+            Err(Error::Corrupt(_))
+        ));
+        assert!(matches!(parse("colour = 1", false), Err(Error::Corrupt(_))));
+    }
+}
@@ -0,0 +1,403 @@
+//! How the cells of a board are laid out and which of them touch.
+
+/// The steps from a cell to each of its eight neighbors on a grid.
+const STEPS: [(isize, isize); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+/// The steps from a hexagon in an even row to its six neighbors. Odd rows sit
+/// half a cell to the right, so the rows above and below them lean right.
+const HEX_STEPS_EVEN: [(isize, isize); 6] = [(-1, -1), (-1, 0), (0, -1), (0, 1), (1, -1), (1, 0)];
+const HEX_STEPS_ODD: [(isize, isize); 6] = [(-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0), (1, 1)];
+
+/// The kind of tile a board is made of.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Shape {
+    /// Squares, touching eight others.
+    #[default]
+    Square,
+    /// Hexagons in rows, touching six others, with every odd row shifted
+    /// half a cell to the right.
+    Hex,
+}
+
+/// Where a cell is: its layer, row and column. On boards a single layer deep
+/// a `(row, column)` pair will do, see the [`From`] impl.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pos {
+    pub layer: usize,
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Pos {
+    pub fn new(layer: usize, row: usize, col: usize) -> Pos {
+        Pos { layer, row, col }
+    }
+}
+
+/// A cell of the first (or only) layer.
+impl From<(usize, usize)> for Pos {
+    fn from((row, col): (usize, usize)) -> Pos {
+        Pos::new(0, row, col)
+    }
+}
+
+/// The shape of a board: its cells, addressed by [`Pos`], and which of them
+/// are neighbors. Cells are stored layer by layer, row by row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Topology {
+    /// `(rows, columns)` of each layer.
+    size: (usize, usize),
+    shape: Shape,
+    /// Whether stepping off one edge comes back on the opposite one.
+    wrap: bool,
+    layers: usize,
+}
+
+impl Topology {
+    /// A `rows` by `columns` rectangle, where every cell touches the up to
+    /// eight cells around it.
+    pub fn rect(size: (usize, usize)) -> Topology {
+        Topology::new(size, Shape::Square, false)
+    }
+
+    /// A `rows` by `columns` rectangle whose edges wrap around, top to bottom
+    /// and left to right, so that every cell of a board at least three cells
+    /// each way has eight neighbors.
+    pub fn torus(size: (usize, usize)) -> Topology {
+        Topology::new(size, Shape::Square, true)
+    }
+
+    /// `rows` by `columns` cells of `shape`, wrapping around if `wrap` is
+    /// set. Hexagons only line up across the top and bottom edges when there
+    /// is an even number of rows.
+    pub fn new(size: (usize, usize), shape: Shape, wrap: bool) -> Topology {
+        Topology {
+            size,
+            shape,
+            wrap,
+            layers: 1,
+        }
+    }
+
+    /// `layers` copies of this board stacked on top of each other. A cell
+    /// also touches the cells right above and below it and their neighbors,
+    /// which makes up to 26 on squares. Only the edges within a layer wrap.
+    pub fn with_layers(self, layers: usize) -> Topology {
+        Topology { layers, ..self }
+    }
+
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    /// `(rows, columns)` of each layer.
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers * self.size.0 * self.size.1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, index: impl Into<Pos>) -> bool {
+        let index = index.into();
+        index.layer < self.layers && index.row < self.size.0 && index.col < self.size.1
+    }
+
+    /// Where the cell at `index` is stored.
+    pub fn offset(&self, index: impl Into<Pos>) -> usize {
+        let index = index.into();
+        debug_assert!(self.contains(index), "{index:?} is off the board");
+        (index.layer * self.size.0 + index.row) * self.size.1 + index.col
+    }
+
+    /// Every cell, in storage order.
+    pub fn indices(&self) -> impl Iterator<Item = Pos> {
+        let (rows, cols) = self.size;
+        (0..self.layers).flat_map(move |l| {
+            (0..rows).flat_map(move |r| (0..cols).map(move |c| Pos::new(l, r, c)))
+        })
+    }
+
+    /// The cells touching `index`, not including itself. On a torus narrower
+    /// than three cells, steps that come back to the same cell count once.
+    pub fn neighbors(&self, index: impl Into<Pos>) -> Vec<Pos> {
+        let index = index.into();
+        let flat = self.flat_neighbors((index.row, index.col));
+        let layers = index.layer.saturating_sub(1)..(index.layer + 2).min(self.layers);
+
+        let mut neighbors = Vec::with_capacity(flat.len() * layers.len() + 2);
+        for layer in layers {
+            let above_or_below = (layer != index.layer).then_some((index.row, index.col));
+            for (row, col) in above_or_below.into_iter().chain(flat.iter().copied()) {
+                neighbors.push(Pos::new(layer, row, col));
+            }
+        }
+        neighbors
+    }
+
+    /// The neighbors of `(row, column)` within a layer.
+    fn flat_neighbors(&self, index: (usize, usize)) -> Vec<(usize, usize)> {
+        let steps: &[(isize, isize)] = match self.shape {
+            Shape::Square => &STEPS,
+            Shape::Hex if index.0.is_multiple_of(2) => &HEX_STEPS_EVEN,
+            Shape::Hex => &HEX_STEPS_ODD,
+        };
+        let mut neighbors = Vec::with_capacity(steps.len());
+        for &(dr, dc) in steps {
+            match self.step(index, dr, dc) {
+                Some(n) if n != index && !neighbors.contains(&n) => neighbors.push(n),
+                _ => (),
+            }
+        }
+        neighbors
+    }
+
+    /// The cell `dr` rows and `dc` columns away within a layer, if it's on
+    /// the board.
+    fn step(&self, index: (usize, usize), dr: isize, dc: isize) -> Option<(usize, usize)> {
+        if self.wrap {
+            let wrap =
+                |i: usize, d: isize, len: usize| (i as isize + d).rem_euclid(len as isize) as usize;
+            return Some((
+                wrap(index.0, dr, self.size.0),
+                wrap(index.1, dc, self.size.1),
+            ));
+        }
+        let r = index.0.checked_add_signed(dr)?;
+        let c = index.1.checked_add_signed(dc)?;
+        (r < self.size.0 && c < self.size.1).then_some((r, c))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Sizes to check everything against, including single rows and columns
+    /// and boards wider than they are tall and the other way round.
+    const SIZES: [(usize, usize); 9] = [
+        (1, 1),
+        (1, 2),
+        (2, 1),
+        (1, 7),
+        (7, 1),
+        (2, 2),
+        (3, 5),
+        (5, 3),
+        (16, 30),
+    ];
+
+    /// Cells of the first layer.
+    fn cells(indices: &[(usize, usize)]) -> Vec<Pos> {
+        indices.iter().map(|&i| Pos::from(i)).collect()
+    }
+
+    #[test]
+    fn test_neighbors_match_distance() {
+        for size in SIZES {
+            let t = Topology::rect(size);
+            for a in t.indices() {
+                let mut expected: Vec<_> = t
+                    .indices()
+                    .filter(|&b| b != a && a.row.abs_diff(b.row) <= 1 && a.col.abs_diff(b.col) <= 1)
+                    .collect();
+                let mut neighbors = t.neighbors(a);
+                expected.sort();
+                neighbors.sort();
+                assert_eq!(neighbors, expected, "{a:?} on {size:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_corners_edges_and_middle() {
+        let t = Topology::rect((4, 6));
+        for i in [(0, 0), (0, 5), (3, 0), (3, 5)] {
+            assert_eq!(t.neighbors(i).len(), 3, "corner {i:?}");
+        }
+        for i in [(0, 2), (3, 3), (1, 0), (2, 5)] {
+            assert_eq!(t.neighbors(i).len(), 5, "edge {i:?}");
+        }
+        assert_eq!(t.neighbors((2, 3)).len(), 8);
+
+        assert!(Topology::rect((1, 1)).neighbors((0, 0)).is_empty());
+        assert_eq!(
+            Topology::rect((1, 5)).neighbors((0, 2)),
+            cells(&[(0, 1), (0, 3)])
+        );
+        assert_eq!(
+            Topology::rect((5, 1)).neighbors((2, 0)),
+            cells(&[(1, 0), (3, 0)])
+        );
+    }
+
+    #[test]
+    fn test_torus_neighbors() {
+        for size in SIZES {
+            let t = Topology::torus(size);
+            let (rows, cols) = size;
+            // The distance between two lines, going whichever way is shorter.
+            let near = |a: usize, b: usize, len: usize| {
+                let d = a.abs_diff(b);
+                d.min(len - d) <= 1
+            };
+            for a in t.indices() {
+                let mut expected: Vec<_> = t
+                    .indices()
+                    .filter(|&b| b != a && near(a.row, b.row, rows) && near(a.col, b.col, cols))
+                    .collect();
+                let mut neighbors = t.neighbors(a);
+                expected.sort();
+                neighbors.sort();
+                assert_eq!(neighbors, expected, "{a:?} on {size:?}");
+                if rows >= 3 && cols >= 3 {
+                    assert_eq!(neighbors.len(), 8, "{a:?} on {size:?}");
+                }
+            }
+        }
+
+        let mut corner = Topology::torus((4, 6)).neighbors((0, 0));
+        corner.sort();
+        assert_eq!(
+            corner,
+            cells(&[
+                (0, 1),
+                (0, 5),
+                (1, 0),
+                (1, 1),
+                (1, 5),
+                (3, 0),
+                (3, 1),
+                (3, 5)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_hex_neighbors() {
+        for size in SIZES {
+            for wrap in [false, true] {
+                if wrap && size.0 % 2 == 1 {
+                    continue;
+                }
+                let t = Topology::new(size, Shape::Hex, wrap);
+                for a in t.indices() {
+                    let neighbors = t.neighbors(a);
+                    assert!(neighbors.len() <= 6, "{a:?} on {size:?}");
+                    for b in neighbors {
+                        assert!(t.neighbors(b).contains(&a), "{a:?} and {b:?} on {size:?}");
+                    }
+                }
+            }
+        }
+
+        let t = Topology::new((5, 5), Shape::Hex, false);
+        let mut even = t.neighbors((2, 2));
+        even.sort();
+        assert_eq!(
+            even,
+            cells(&[(1, 1), (1, 2), (2, 1), (2, 3), (3, 1), (3, 2)])
+        );
+        let mut odd = t.neighbors((1, 2));
+        odd.sort();
+        assert_eq!(
+            odd,
+            cells(&[(0, 2), (0, 3), (1, 1), (1, 3), (2, 2), (2, 3)])
+        );
+        assert_eq!(t.neighbors((0, 0)), cells(&[(0, 1), (1, 0)]));
+
+        let t = Topology::new((4, 5), Shape::Hex, true);
+        assert!(t.indices().all(|i| t.neighbors(i).len() == 6));
+        let mut corner = t.neighbors((0, 0));
+        corner.sort();
+        assert_eq!(
+            corner,
+            cells(&[(0, 1), (0, 4), (1, 0), (1, 4), (3, 0), (3, 4)])
+        );
+    }
+
+    #[test]
+    fn test_layers() {
+        for size in SIZES {
+            for layers in [1, 2, 3] {
+                let t = Topology::rect(size).with_layers(layers);
+                let at = |i: Pos| [i.layer, i.row, i.col];
+                for a in t.indices() {
+                    let mut expected: Vec<_> = t
+                        .indices()
+                        .filter(|&b| b != a && (0..3).all(|k| at(a)[k].abs_diff(at(b)[k]) <= 1))
+                        .collect();
+                    let mut neighbors = t.neighbors(a);
+                    expected.sort();
+                    neighbors.sort();
+                    assert_eq!(neighbors, expected, "{a:?} on {size:?} x {layers}");
+                }
+            }
+        }
+
+        let t = Topology::rect((3, 3)).with_layers(3);
+        assert_eq!(t.size(), (3, 3));
+        assert_eq!(t.len(), 27);
+        assert_eq!(t.neighbors(Pos::new(1, 1, 1)).len(), 26);
+        assert_eq!(t.neighbors((0, 0)).len(), 7);
+        assert!(t.contains(Pos::new(2, 2, 2)));
+        assert!(!t.contains(Pos::new(3, 0, 0)));
+
+        // Hexagons keep the same row parity in every layer.
+        let t = Topology::new((3, 3), Shape::Hex, false).with_layers(2);
+        let mut odd = t.neighbors(Pos::new(1, 1, 1));
+        odd.sort();
+        let flat = [(0, 1), (0, 2), (1, 0), (1, 2), (2, 1), (2, 2)];
+        let mut expected: Vec<_> = [0, 1]
+            .into_iter()
+            .flat_map(|l| flat.map(|(r, c)| Pos::new(l, r, c)))
+            .chain([Pos::new(0, 1, 1)])
+            .collect();
+        expected.sort();
+        assert_eq!(odd, expected);
+    }
+
+    #[test]
+    fn test_offsets() {
+        for size in SIZES {
+            let t = Topology::rect(size);
+            let offsets: Vec<_> = t.indices().map(|i| t.offset(i)).collect();
+            assert_eq!(offsets, (0..t.len()).collect::<Vec<_>>(), "{size:?}");
+        }
+
+        // Rows are as long as the board is wide.
+        let t = Topology::rect((16, 30));
+        assert_eq!(t.offset((1, 0)), 30);
+        assert_eq!(t.offset((15, 29)), 479);
+        assert!(!t.contains((16, 0)));
+        assert!(!t.contains((0, 30)));
+
+        // Then layers, one after the other.
+        let t = Topology::rect((16, 30)).with_layers(2);
+        assert_eq!(t.offset(Pos::new(1, 0, 0)), 480);
+        let offsets: Vec<_> = t.indices().map(|i| t.offset(i)).collect();
+        assert_eq!(offsets, (0..t.len()).collect::<Vec<_>>());
+    }
+}
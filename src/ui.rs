@@ -0,0 +1,1949 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use cursive::{
+    align::HAlign,
+    direction::Direction,
+    event::{Event, EventResult, Key, MouseButton, MouseEvent},
+    theme::ColorStyle,
+    view::{Nameable, Resizable},
+    views::{
+        Button, Checkbox, Dialog, DummyView, EditView, LinearLayout, PaddedView, Panel, SelectView,
+        TextContent, TextView,
+    },
+    Cursive, Vec2, View, XY,
+};
+use mines::{
+    flags_add_up, probabilities, CellContents, CellState, Config, Difficulty, Game, GameCode, Hint,
+    OddsError, Pos, Probabilities, SafeStart, Shape, Status, MAX_MINES_PER_CELL,
+};
+
+use crate::{
+    replay::{self, Replay},
+    save::{self, SavedGame},
+    scores::{Score, Scores},
+    settings::{self, Bindings, Keys, Settings},
+    storage,
+    theme::{self, Builtin, Theme},
+};
+
+/// Options for this session, from the command line and the start menu,
+/// stored as the cursive user data.
+pub struct Options {
+    /// Use this seed for every new game instead of a random one.
+    pub seed: Option<u64>,
+    pub settings: Settings,
+    /// Loaded from the theme setting, unless one was given on the command
+    /// line.
+    pub theme: Theme,
+}
+
+/// Everything needed to start a game, and to start the same kind of game
+/// again from the victory dialog.
+#[derive(Debug, Clone)]
+struct GameSetup {
+    config: Config,
+    /// The preset the game was started from. Only preset games with a random
+    /// seed, the usual random layout and one mine per cell go on the
+    /// leaderboard.
+    difficulty: Option<Difficulty>,
+    /// A fixed seed, from `--seed` or a game code.
+    seed: Option<u64>,
+    /// Allow undo and redo.
+    practice: bool,
+}
+
+impl GameSetup {
+    fn ranked(&self) -> Option<Category> {
+        self.difficulty
+            .filter(|_| {
+                self.seed.is_none()
+                    && !self.config.no_guess
+                    && self.config.max_mines == 1
+                    && self.config.layers == 1
+            })
+            .map(|difficulty| Category {
+                difficulty,
+                shape: self.config.shape,
+                torus: self.config.torus,
+            })
+    }
+}
+
+/// A leaderboard: each preset has one for every shape of cell, with and
+/// without wrapping around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Category {
+    difficulty: Difficulty,
+    shape: Shape,
+    torus: bool,
+}
+
+impl Category {
+    fn all() -> impl Iterator<Item = Category> {
+        [Shape::Square, Shape::Hex].into_iter().flat_map(|shape| {
+            [false, true].into_iter().flat_map(move |torus| {
+                Difficulty::ALL.into_iter().map(move |difficulty| Category {
+                    difficulty,
+                    shape,
+                    torus,
+                })
+            })
+        })
+    }
+
+    /// Also the key in the scores file, where the usual boards keep the
+    /// plain difficulty names they have always had.
+    fn name(&self) -> String {
+        let d = self.difficulty.name();
+        match (self.shape, self.torus) {
+            (Shape::Square, false) => d.to_string(),
+            (Shape::Square, true) => format!("{d} (torus)"),
+            (Shape::Hex, false) => format!("{d} (hex)"),
+            (Shape::Hex, true) => format!("{d} (hex torus)"),
+        }
+    }
+}
+
+pub fn start_menu(s: &mut Cursive) {
+    s.pop_layer();
+    let difficulty = s
+        .user_data::<Options>()
+        .map_or(Difficulty::Beginner, |o| o.settings.difficulty);
+
+    let mut select = SelectView::new()
+        .h_align(HAlign::Left)
+        .item("Beginner", Some(Difficulty::Beginner))
+        .item("Intermediate", Some(Difficulty::Intermediate))
+        .item("Expert", Some(Difficulty::Expert))
+        .item("Custom...", None)
+        .on_submit(|s, d: &Option<Difficulty>| match d {
+            Some(d) => new_game(s, d),
+            None => custom_game(s),
+        });
+    select.set_selection(
+        Difficulty::ALL
+            .iter()
+            .position(|&d| d == difficulty)
+            .unwrap_or(0),
+    );
+
+    let mut menu = LinearLayout::vertical();
+    if save::exists() {
+        menu.add_child(PaddedView::lrtb(
+            0,
+            0,
+            1,
+            0,
+            Button::new("Resume", resume_game),
+        ));
+    }
+
+    s.add_layer(
+        Dialog::around(
+            menu.child(PaddedView::lrtb(
+                0,
+                0,
+                1,
+                0,
+                Panel::new(select).title("New Game"),
+            ))
+            .child(
+                LinearLayout::horizontal()
+                    .child(Checkbox::new().with_name("no_guess"))
+                    .child(TextView::new(" No guessing")),
+            )
+            .child(
+                LinearLayout::horizontal()
+                    .child(Checkbox::new().with_name("stacked"))
+                    .child(TextView::new(format!(
+                        " Up to {MAX_MINES_PER_CELL} mines per cell"
+                    ))),
+            )
+            .child(
+                LinearLayout::horizontal()
+                    .child(Checkbox::new().with_name("hex"))
+                    .child(TextView::new(" Hexagonal cells")),
+            )
+            .child(
+                LinearLayout::horizontal()
+                    .child(Checkbox::new().with_name("torus"))
+                    .child(TextView::new(" Torus (edges wrap around)")),
+            )
+            .child(
+                LinearLayout::horizontal()
+                    .child(Checkbox::new().with_name("practice"))
+                    .child(TextView::new(" Practice (u: undo, Ctrl-R: redo)")),
+            )
+            .child(Button::new("Load Game Code", load_code))
+            .child(Button::new("Watch Replay", watch_replay))
+            .child(Button::new("Top Scores", top_scores))
+            .child(Button::new("Settings", show_settings))
+            .child(Button::new("Quit", |s| s.quit())),
+        )
+        .title("Mines!"),
+    );
+}
+
+/// Shows the settings form on top of the start menu. Saving writes the
+/// settings file and applies it from the next game on.
+fn show_settings(s: &mut Cursive) {
+    fn checkbox(name: &str, label: &str, checked: bool) -> LinearLayout {
+        LinearLayout::horizontal()
+            .child(Checkbox::new().with_checked(checked).with_name(name))
+            .child(TextView::new(format!(" {label}")))
+    }
+
+    fn choice<T: PartialEq + 'static>(
+        label: &str,
+        name: &str,
+        items: Vec<(String, T)>,
+        current: &T,
+    ) -> LinearLayout {
+        let index = items.iter().position(|(_, v)| v == current).unwrap_or(0);
+        let mut select = SelectView::new().popup();
+        select.add_all(items);
+        select.set_selection(index);
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_width(18))
+            .child(select.with_name(name))
+    }
+
+    let settings = s
+        .user_data::<Options>()
+        .map(|o| o.settings.clone())
+        .unwrap_or_default();
+
+    let mut themes: Vec<_> = Builtin::ALL
+        .iter()
+        .map(|b| (b.name().to_string(), b.name().to_string()))
+        .collect();
+    themes.push((
+        format!("{} (theme.toml)", theme::CUSTOM),
+        theme::CUSTOM.to_string(),
+    ));
+    let widths = vec![
+        ("3 columns: [1]".to_string(), 3),
+        ("2 columns: 1".to_string(), 2),
+        ("1 column: 1".to_string(), 1),
+    ];
+    let keys = Keys::ALL
+        .iter()
+        .map(|k| (k.describe().to_string(), *k))
+        .collect();
+    let difficulties = Difficulty::ALL
+        .iter()
+        .map(|d| (d.name().to_string(), *d))
+        .collect();
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(checkbox(
+                    "set_safe_area",
+                    "Open an area on first click",
+                    settings.safe_start == SafeStart::Area,
+                ))
+                .child(checkbox(
+                    "set_question_marks",
+                    "Question marks",
+                    settings.question_marks,
+                ))
+                .child(checkbox(
+                    "set_swap_buttons",
+                    "Swap mouse buttons",
+                    settings.bindings.swap_buttons,
+                ))
+                .child(choice("Theme:", "set_theme", themes, &settings.theme))
+                .child(choice("Cells:", "set_width", widths, &settings.cell_width))
+                .child(choice(
+                    "Movement keys:",
+                    "set_keys",
+                    keys,
+                    &settings.bindings.keys,
+                ))
+                .child(choice(
+                    "Difficulty:",
+                    "set_difficulty",
+                    difficulties,
+                    &settings.difficulty,
+                )),
+        )
+        .title("Settings")
+        .button("Save", save_settings)
+        .dismiss_button("Back"),
+    );
+}
+
+/// Reads the settings form, and saves and applies it if its theme loads.
+fn save_settings(s: &mut Cursive) {
+    fn checked(s: &mut Cursive, name: &str) -> bool {
+        s.call_on_name(name, |v: &mut Checkbox| v.is_checked())
+            .unwrap_or(false)
+    }
+
+    fn selected<T: Clone + 'static>(s: &mut Cursive, name: &str) -> Option<T> {
+        s.call_on_name(name, |v: &mut SelectView<T>| v.selection())
+            .flatten()
+            .map(|v| (*v).clone())
+    }
+
+    let default = Settings::default();
+    let settings = Settings {
+        safe_start: if checked(s, "set_safe_area") {
+            SafeStart::Area
+        } else {
+            SafeStart::Cell
+        },
+        question_marks: checked(s, "set_question_marks"),
+        theme: selected(s, "set_theme").unwrap_or(default.theme),
+        cell_width: selected(s, "set_width").unwrap_or(default.cell_width),
+        bindings: Bindings {
+            keys: selected(s, "set_keys").unwrap_or(default.bindings.keys),
+            swap_buttons: checked(s, "set_swap_buttons"),
+        },
+        difficulty: selected(s, "set_difficulty").unwrap_or(default.difficulty),
+    };
+
+    let theme = match theme::load(&settings.theme) {
+        Ok(theme) => theme,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Could not load the theme:\n{e}")));
+            return;
+        }
+    };
+    let res = settings::save(&settings);
+    s.with_user_data(|o: &mut Options| {
+        o.settings = settings;
+        o.theme = theme;
+    });
+
+    s.pop_layer();
+    start_menu(s);
+    if let Err(e) = res {
+        s.add_layer(Dialog::info(format!("Could not save the settings:\n{e}")));
+    }
+}
+
+/// Lists the entries of the settings file that were ignored.
+pub fn report_settings(s: &mut Cursive, problems: &[String]) {
+    let path = settings::path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "the settings file".to_string());
+    s.add_layer(
+        Dialog::info(format!(
+            "Some settings in {path} were ignored:\n{}",
+            problems.join("\n")
+        ))
+        .title("Settings"),
+    );
+}
+
+fn top_scores(s: &mut Cursive) {
+    show_scores(
+        s,
+        Category {
+            difficulty: Difficulty::Beginner,
+            shape: Shape::Square,
+            torus: false,
+        },
+    );
+}
+
+fn show_scores(s: &mut Cursive, category: Category) {
+    s.pop_layer();
+
+    let scores = match Scores::load() {
+        Ok(scores) => scores,
+        Err(e) => {
+            s.add_layer(
+                Dialog::text(format!("Could not read high scores:\n{e}"))
+                    .title("High Scores")
+                    .button("Back", start_menu),
+            );
+            return;
+        }
+    };
+
+    let table = TextView::new(score_table(&scores, category)).with_name("score_table");
+    let mut select = SelectView::new().on_select(move |s, category: &Category| {
+        s.call_on_name("score_table", |v: &mut TextView| {
+            v.set_content(score_table(&scores, *category))
+        });
+    });
+    for c in Category::all() {
+        select.add_item(c.name(), c);
+    }
+    select.set_selection(Category::all().position(|c| c == category).unwrap_or(0));
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::horizontal()
+                .child(Panel::new(select))
+                .child(Panel::new(table.min_width(40))),
+        )
+        .title("High Scores")
+        .button("Back", start_menu),
+    )
+}
+
+fn score_table(scores: &Scores, category: Category) -> String {
+    let list = scores.get(&category.name());
+    if list.is_empty() {
+        return "No wins yet.".to_string();
+    }
+
+    list.iter()
+        .enumerate()
+        .map(|(i, score)| {
+            format!(
+                "{:>2}. {:<16} {:>7.1}s  {}{}",
+                i + 1,
+                score.name,
+                score.seconds(),
+                score.date,
+                match score.hints {
+                    0 => String::new(),
+                    n => format!("  ({n} hints)"),
+                }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Shows the loss dialog next to the board, so the mines stay in view.
+fn blow_up(s: &mut Cursive, setup: GameSetup) {
+    let dialog = Dialog::text("!!!! BOOOM !!!!")
+        .button("Try Again", move |s| start_game(s, setup.clone()))
+        .button("Menu", start_menu);
+    s.call_on_name("game", |row: &mut LinearLayout| {
+        row.add_child(dialog);
+        let _ = row.set_focus_index(row.len() - 1);
+    });
+}
+
+/// Shows the victory dialog. Only the preset difficulties have a
+/// leaderboard; custom boards and games with undos just report their time.
+fn game_won(s: &mut Cursive, setup: GameSetup, time: Duration, hints: u32, undos: u32) {
+    let d = setup.ranked().filter(|_| undos == 0);
+    let qualifies = d.is_some_and(|d| {
+        Scores::load_or_reset()
+            .map(|scores| scores.qualifies(&d.name(), time))
+            .unwrap_or(false)
+    });
+
+    let mut content = LinearLayout::vertical().child(TextView::new(format!(
+        "All mines found in {:.1}s{}!",
+        time.as_secs_f64(),
+        match hints {
+            0 => String::new(),
+            1 => " with 1 hint".to_string(),
+            n => format!(" with {n} hints"),
+        }
+    )));
+    if undos > 0 {
+        content.add_child(TextView::new("Moves were undone, so it isn't ranked."));
+    }
+    if qualifies {
+        content.add_child(PaddedView::lrtb(
+            0,
+            0,
+            1,
+            0,
+            TextView::new("New high score! Your name:"),
+        ));
+        content.add_child(
+            EditView::new()
+                .max_content_width(16)
+                .with_name("player_name")
+                .fixed_width(20),
+        );
+    }
+
+    s.add_layer(
+        Dialog::around(content)
+            .title("You Win!")
+            .button("Play Again", move |s| {
+                let res = d.map_or(Ok(false), |d| record_score(s, d, time, hints));
+                s.pop_layer();
+                start_game(s, setup.clone());
+                if let Err(e) = res {
+                    s.add_layer(Dialog::info(format!("Could not save score:\n{e}")));
+                }
+            })
+            .button("Menu", move |s| {
+                let res = d.map_or(Ok(false), |d| record_score(s, d, time, hints));
+                s.pop_layer();
+                match (res, d) {
+                    (Ok(true), Some(d)) => show_scores(s, d),
+                    (Ok(_), _) => start_menu(s),
+                    (Err(e), _) => {
+                        start_menu(s);
+                        s.add_layer(Dialog::info(format!("Could not save score:\n{e}")));
+                    }
+                }
+            }),
+    );
+}
+
+/// Saves the name typed into the victory dialog, if it asked for one.
+/// Returns whether a score was recorded.
+fn record_score(
+    s: &mut Cursive,
+    d: Category,
+    time: Duration,
+    hints: u32,
+) -> Result<bool, storage::Error> {
+    let Some(name) = s.call_on_name("player_name", |v: &mut EditView| v.get_content()) else {
+        return Ok(false);
+    };
+    let name = match name.trim() {
+        "" => "Anonymous",
+        name => name,
+    };
+
+    let mut scores = Scores::load_or_reset()?;
+    scores.insert(&d.name(), Score::new(name, time, hints));
+    scores.save()?;
+    Ok(true)
+}
+
+struct Grid {
+    game: Game,
+    setup: GameSetup,
+    /// "Mines left" and the clock, shown above the board.
+    status: TextContent,
+    /// The cell that keyboard commands act on, whose layer is the one shown.
+    cursor: Pos,
+    /// Mouse buttons currently held, to detect both being pressed at once.
+    left_down: bool,
+    right_down: bool,
+    /// The cell picked out by the last hint, until the next move.
+    hint: Option<Hint>,
+    /// Shown after the status line until the next move.
+    message: Option<&'static str>,
+    /// The chance of a mine under each hidden cell, while the overlay is on,
+    /// or why they couldn't be worked out. Kept until the board changes.
+    odds: Option<Result<Probabilities, OddsError>>,
+    theme: Theme,
+    glyphs: Glyphs,
+    bindings: Bindings,
+}
+
+/// Shown instead of the loss dialog in practice mode.
+const BOOM: &str = "Boom! Press u to take that back.";
+
+/// The text for each kind of cell, all [`Glyphs::width`] columns wide.
+#[derive(Debug, Clone)]
+struct Glyphs {
+    width: usize,
+    /// Up to 26, for every neighbor of a cell in the middle of a layered
+    /// board.
+    numbers: Vec<String>,
+    hidden: String,
+    /// `flags[n - 1]` marks `n` mines: one more stroke for each.
+    flags: Vec<String>,
+    question: String,
+    mine: String,
+    wrong_flag: String,
+}
+
+impl Glyphs {
+    /// `[1]` in three columns, `1 ` in two and `1` in one. Numbers from 10
+    /// are left as they are where they fit and go on from `9` to `A`, `B` and
+    /// so on in a single column.
+    fn new(width: usize) -> Glyphs {
+        let glyph = |c: char| match width {
+            1 => c.to_string(),
+            2 => format!("{c} "),
+            _ => format!("[{c}]"),
+        };
+        let number = |n: u32| match (n, width) {
+            (0..=9, _) => glyph(char::from_digit(n, 10).unwrap()),
+            (_, 1) => char::from_digit(n, 36)
+                .unwrap()
+                .to_ascii_uppercase()
+                .to_string(),
+            (_, 2) => n.to_string(),
+            _ => format!("{n:<3}"),
+        };
+        Glyphs {
+            width,
+            numbers: (0..=26).map(number).collect(),
+            hidden: glyph('#'),
+            flags: ['~', '=', '≡'].into_iter().map(glyph).collect(),
+            question: glyph('?'),
+            mine: glyph('*'),
+            wrong_flag: glyph('X'),
+        }
+    }
+}
+
+impl View for Grid {
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, cursive::view::CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, e: Event) -> EventResult {
+        let res = match self.bindings.translate(e) {
+            Event::Mouse {
+                offset,
+                position,
+                event,
+            } => {
+                // Releases count wherever they happen, so a button let go off
+                // the board doesn't stay down.
+                if let MouseEvent::Release(button) = event {
+                    match button {
+                        MouseButton::Left => self.left_down = false,
+                        MouseButton::Right => self.right_down = false,
+                        _ => (),
+                    }
+                    return EventResult::Consumed(None);
+                }
+                let Some(index) = position
+                    .checked_sub(offset + self.origin())
+                    .and_then(|p| self.geometry().cell_at(p))
+                    .map(|i| self.on_layer(i))
+                else {
+                    return EventResult::Ignored;
+                };
+
+                match event {
+                    MouseEvent::Press(button) => {
+                        self.cursor = index;
+                        match button {
+                            MouseButton::Left if self.right_down => {
+                                self.left_down = true;
+                                self.chord(index)
+                            }
+                            MouseButton::Right if self.left_down => {
+                                self.right_down = true;
+                                self.chord(index)
+                            }
+                            MouseButton::Left => {
+                                self.left_down = true;
+                                self.open(index)
+                            }
+                            MouseButton::Right => {
+                                self.right_down = true;
+                                self.toggle_flag(index)
+                            }
+                            MouseButton::Middle => self.chord(index),
+                            _ => EventResult::Ignored,
+                        }
+                    }
+                    _ => EventResult::Ignored,
+                }
+            }
+            Event::Key(Key::Up) => self.move_cursor(-1, 0),
+            Event::Key(Key::Down) => self.move_cursor(1, 0),
+            Event::Key(Key::Left) => self.move_cursor(0, -1),
+            Event::Key(Key::Right) => self.move_cursor(0, 1),
+            Event::Key(Key::PageUp) => self.change_layer(-1),
+            Event::Key(Key::PageDown) => self.change_layer(1),
+            Event::Char(' ') | Event::Key(Key::Enter) => {
+                if self.game.board()[self.cursor].state == CellState::Revealed {
+                    self.chord(self.cursor)
+                } else {
+                    self.open(self.cursor)
+                }
+            }
+            Event::Char('f') => self.toggle_flag(self.cursor),
+            Event::Char('?') => self.show_hint(),
+            Event::Char('u') => {
+                self.hint = None;
+                self.message = None;
+                self.game.undo();
+                self.update_odds();
+                EventResult::Consumed(None)
+            }
+            Event::CtrlChar('r') => {
+                self.hint = None;
+                self.message = None;
+                self.game.redo();
+                self.update_odds();
+                if self.game.status() == Status::Lost {
+                    self.message = Some(BOOM);
+                }
+                EventResult::Consumed(None)
+            }
+            Event::Char('p') => {
+                self.odds = match self.odds {
+                    Some(_) => None,
+                    None => Some(probabilities(
+                        self.game.board(),
+                        self.game.config().num_bombs,
+                    )),
+                };
+                EventResult::Consumed(None)
+            }
+            _ => EventResult::Ignored,
+        };
+
+        self.update_status();
+        res
+    }
+
+    fn draw(&self, printer: &cursive::Printer) {
+        let (r, c) = self.size();
+        let geometry = self.geometry();
+        if self.wraps() {
+            // Dashed edges on every side, as a reminder that they are open.
+            let size = geometry.size();
+            printer.with_color(ColorStyle::secondary(), |printer| {
+                let across = "╌".repeat(size.x);
+                printer.print((1, 0), &across);
+                printer.print((1, r + 1), &across);
+                for x in 1..=r {
+                    printer.print((0, x), "┆");
+                    printer.print((size.x + 1, x), "┆");
+                }
+            });
+        }
+
+        let printer = &printer.offset(self.origin());
+        for x in 0..r {
+            for y in 0..c {
+                let index = self.on_layer((x, y));
+                let odds = match &self.odds {
+                    Some(Ok(odds)) if !self.game.is_over() => {
+                        odds.get(index).map(|p| percent(p, self.glyphs.width))
+                    }
+                    _ => None,
+                };
+                let (text, style) = self.look(index, odds.as_deref());
+
+                let hinted = matches!(
+                    self.hint,
+                    Some(Hint::Safe(i) | Hint::Mine(i, _)) if i == index
+                );
+                let at = geometry.position((x, y));
+                if printer.focused && self.cursor == index {
+                    printer.with_color(ColorStyle::highlight(), |printer| printer.print(at, text));
+                } else if hinted {
+                    printer.with_color(ColorStyle::secondary(), |printer| printer.print(at, text));
+                } else {
+                    printer.with_color(style, |printer| printer.print(at, text));
+                }
+            }
+        }
+    }
+
+    fn required_size(&mut self, _: Vec2) -> Vec2 {
+        self.geometry().size() + self.origin() * 2
+    }
+
+    fn layout(&mut self, _: Vec2) {}
+
+    fn needs_relayout(&self) -> bool {
+        true
+    }
+
+    fn call_on_any(&mut self, _: &cursive::view::Selector, _: cursive::event::AnyCb) {}
+
+    fn focus_view(
+        &mut self,
+        _: &cursive::view::Selector,
+    ) -> Result<EventResult, cursive::view::ViewNotFound> {
+        Err(cursive::view::ViewNotFound)
+    }
+
+    fn important_area(&self, view_size: Vec2) -> cursive::Rect {
+        cursive::Rect::from_size((0, 0), view_size)
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+impl Grid {
+    fn new(setup: GameSetup) -> Grid {
+        let config = setup.config.clone();
+        let mut game = match setup.seed {
+            Some(seed) => Game::with_seed(config, seed),
+            None => Game::new(config),
+        };
+        if setup.practice {
+            game.enable_practice();
+        }
+        Grid::with_game(setup, game)
+    }
+
+    fn with_game(setup: GameSetup, game: Game) -> Grid {
+        Grid {
+            game,
+            setup,
+            status: TextContent::new(""),
+            cursor: Pos::default(),
+            left_down: false,
+            right_down: false,
+            hint: None,
+            message: None,
+            odds: None,
+            theme: Theme::default(),
+            glyphs: Glyphs::new(3),
+            bindings: Bindings::default(),
+        }
+    }
+
+    /// `(rows, columns)` of each layer.
+    fn size(&self) -> (usize, usize) {
+        self.game.board().size()
+    }
+
+    /// The cell at `(row, column)` of the layer on screen.
+    fn on_layer(&self, (r, c): (usize, usize)) -> Pos {
+        Pos::new(self.cursor.layer, r, c)
+    }
+
+    fn wraps(&self) -> bool {
+        self.game.board().topology().wraps()
+    }
+
+    fn geometry(&self) -> Geometry {
+        Geometry::new(self.game.config(), self.glyphs.width)
+    }
+
+    /// Where the first cell is drawn, leaving room for the wrap indicators
+    /// around a torus.
+    fn origin(&self) -> Vec2 {
+        if self.wraps() {
+            Vec2::new(1, 1)
+        } else {
+            Vec2::zero()
+        }
+    }
+
+    /// The text and colors for the cell at `index`, showing `odds` on it if
+    /// it's hidden. After a loss, every mine and wrong flag is shown, except
+    /// in practice games where the loss can be undone. A flag for the wrong
+    /// number of mines counts as wrong.
+    fn look<'a>(&'a self, index: Pos, odds: Option<&'a str>) -> (&'a str, ColorStyle) {
+        let post_mortem = self.game.status() == Status::Lost && !self.game.is_practice();
+        let cell = &self.game.board()[index];
+        let mines = cell.contents.mines();
+        let glyphs = &self.glyphs;
+        match cell.state {
+            CellState::Flagged(n) if post_mortem && n != mines => {
+                (&glyphs.wrong_flag, self.theme.wrong_flag())
+            }
+            CellState::Hidden | CellState::Questioned if post_mortem && mines > 0 => {
+                (&glyphs.mine, self.theme.mine())
+            }
+            CellState::Flagged(n) => {
+                let flag = &glyphs.flags[(n as usize).clamp(1, glyphs.flags.len()) - 1];
+                (flag, self.theme.flag())
+            }
+            CellState::Questioned => (&glyphs.question, self.theme.question()),
+            CellState::Hidden => (odds.unwrap_or(&glyphs.hidden), self.theme.hidden()),
+            CellState::Revealed => match cell.contents {
+                CellContents::Hint(n) => (&glyphs.numbers[n as usize], self.theme.number(n)),
+                CellContents::Bomb(_) if self.game.exploded() == Some(index) => {
+                    (&glyphs.mine, self.theme.exploded())
+                }
+                CellContents::Bomb(_) => (&glyphs.mine, self.theme.mine()),
+            },
+        }
+    }
+
+    fn saved(&self) -> SavedGame {
+        SavedGame {
+            game: self.game.clone(),
+            difficulty: self.setup.difficulty,
+            fixed_seed: self.setup.seed.is_some(),
+        }
+    }
+
+    fn open(&mut self, index: Pos) -> EventResult {
+        self.play(|game| game.reveal(index))
+    }
+
+    fn chord(&mut self, index: Pos) -> EventResult {
+        self.play(|game| game.chord(index))
+    }
+
+    fn toggle_flag(&mut self, index: Pos) -> EventResult {
+        self.hint = None;
+        self.message = None;
+        self.game.toggle_flag(index);
+        self.update_odds();
+        EventResult::Consumed(None)
+    }
+
+    /// Points out a provable move, or says that there isn't one.
+    fn show_hint(&mut self) -> EventResult {
+        if self.game.is_over() {
+            return EventResult::Consumed(None);
+        }
+        if !self.game.is_started() {
+            self.message = Some("Hint: the first reveal is always safe.");
+            return EventResult::Consumed(None);
+        }
+
+        self.hint = self.game.hint();
+        if let Some(Hint::Safe(i) | Hint::Mine(i, _)) = self.hint {
+            // Bring up the layer the hint is on.
+            self.cursor.layer = i.layer;
+        }
+        self.message = Some(match self.hint {
+            Some(Hint::Safe(_)) => "Hint: the marked cell is safe.",
+            Some(Hint::Mine(_, 1)) => "Hint: the marked cell is a mine.",
+            Some(Hint::Mine(_, 2)) => "Hint: the marked cell holds 2 mines.",
+            Some(Hint::Mine(_, _)) => "Hint: the marked cell holds 3 mines.",
+            None if !flags_add_up(self.game.board(), self.game.config().num_bombs) => {
+                "Hint: the flags don't add up, one of them is wrong."
+            }
+            None => "Hint: no move can be proven, you'll have to guess.",
+        });
+        EventResult::Consumed(None)
+    }
+
+    /// Makes a move and opens the victory or loss dialog if it ended the
+    /// game.
+    fn play(&mut self, f: impl FnOnce(&mut Game) -> Status) -> EventResult {
+        if self.game.is_over() {
+            return EventResult::Consumed(None);
+        }
+        self.hint = None;
+        self.message = None;
+
+        let status = f(&mut self.game);
+        self.update_odds();
+        let res = match status {
+            Status::Playing => return EventResult::Consumed(None),
+            Status::Lost if self.game.is_practice() => {
+                self.message = Some(BOOM);
+                EventResult::Consumed(None)
+            }
+            Status::Lost => {
+                let setup = self.setup.clone();
+                EventResult::with_cb(move |s| blow_up(s, setup.clone()))
+            }
+            Status::Won => {
+                let setup = self.setup.clone();
+                let time = self.game.elapsed();
+                let hints = self.game.hints();
+                let undos = self.game.undos();
+                EventResult::with_cb(move |s| game_won(s, setup.clone(), time, hints, undos))
+            }
+        };
+
+        match replay::save(&self.game) {
+            Ok(()) => res,
+            Err(e) => {
+                let message = format!("Could not save the replay:\n{e}");
+                res.and(EventResult::with_cb(move |s| {
+                    s.add_layer(Dialog::info(message.clone()))
+                }))
+            }
+        }
+    }
+
+    /// Moves the cursor, stopping at the edges, or on a torus going through
+    /// them.
+    fn move_cursor(&mut self, dr: isize, dc: isize) -> EventResult {
+        let (rows, cols) = self.size();
+        let Pos { row: r, col: c, .. } = self.cursor;
+        self.cursor = self.on_layer(if self.wraps() {
+            let wrap =
+                |i: usize, d: isize, len: usize| (i as isize + d).rem_euclid(len as isize) as usize;
+            (wrap(r, dr, rows), wrap(c, dc, cols))
+        } else {
+            (
+                r.saturating_add_signed(dr).min(rows - 1),
+                c.saturating_add_signed(dc).min(cols - 1),
+            )
+        });
+        EventResult::Consumed(None)
+    }
+
+    /// Shows the layer `dl` above or below, keeping the cursor in the same
+    /// place on it. Layers don't wrap.
+    fn change_layer(&mut self, dl: isize) -> EventResult {
+        let layers = self.game.board().topology().layers();
+        self.cursor.layer = self.cursor.layer.saturating_add_signed(dl).min(layers - 1);
+        EventResult::Consumed(None)
+    }
+
+    /// Works the odds out again for the board as it is now, if the overlay is
+    /// on. Called after anything that changes the board, and only then, since
+    /// it can take a while.
+    fn update_odds(&mut self) {
+        if self.odds.is_some() {
+            let board = self.game.board();
+            self.odds = Some(probabilities(board, self.game.config().num_bombs));
+        }
+    }
+
+    /// Refreshes the status line. Called after every event and on each
+    /// cursive refresh, to keep the clock ticking.
+    fn update_status(&self) {
+        let note = match self.message {
+            Some(message) => message,
+            None => match self.odds {
+                Some(Err(OddsError::Stacked)) => "(no odds with several mines per cell)",
+                Some(Err(OddsError::Contradiction)) => "(no odds: the flags don't add up)",
+                Some(Err(OddsError::TooManyPossibilities)) => "(too many possibilities for odds)",
+                _ if self.game.fell_back() => "(no guess-free board found)",
+                _ => "",
+            },
+        };
+        let layers = self.game.board().topology().layers();
+        let layer = match layers {
+            1 => String::new(),
+            n => format!("Layer: {}/{n} ", self.cursor.layer + 1),
+        };
+        self.status.set_content(format!(
+            "Mines: {:<4} Time: {:<5} {layer}{note}",
+            self.game.mines_left(),
+            self.game.elapsed().as_secs(),
+        ));
+    }
+}
+
+/// A mine probability in a cell `width` columns wide: ` 7%` in three, ` 7`
+/// in two and a digit out of 10 in one. Only certainties show as 0, or as
+/// 100% where that fits and `!` where it doesn't.
+fn percent(p: f64, width: usize) -> String {
+    const EPSILON: f64 = 1e-9;
+    let (mine, safe) = (p > 1.0 - EPSILON, p < EPSILON);
+    let scaled = |scale: f64| ((p * scale).round() as u32).clamp(1, scale as u32 - 1);
+    match width {
+        1 if mine => "!".to_string(),
+        1 if safe => "0".to_string(),
+        1 => scaled(10.0).to_string(),
+        2 if mine => "!!".to_string(),
+        2 if safe => " 0".to_string(),
+        2 => format!("{:>2}", scaled(100.0)),
+        _ if mine => "100".to_string(),
+        _ if safe => " 0%".to_string(),
+        _ => format!("{:>2}%", scaled(100.0)),
+    }
+}
+
+/// Playback speeds, as multiples of real time.
+const SPEEDS: [f64; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+
+/// Plays a [`Replay`] back, leaving the drawing to a [`Grid`] that the moves
+/// are made on.
+struct ReplayView {
+    grid: Grid,
+    replay: Replay,
+    /// How many of the moves have been made.
+    next: usize,
+    /// How far into the game playback has got.
+    position: Duration,
+    /// Index into [`SPEEDS`].
+    speed: usize,
+    paused: bool,
+    last_tick: Instant,
+    status: TextContent,
+}
+
+impl ReplayView {
+    fn new(replay: Replay) -> ReplayView {
+        let setup = GameSetup {
+            config: replay.code.config.clone(),
+            difficulty: None,
+            seed: Some(replay.code.seed),
+            practice: false,
+        };
+        ReplayView {
+            grid: Grid::with_game(setup, replay.start()),
+            replay,
+            next: 0,
+            position: Duration::ZERO,
+            speed: 2,
+            paused: false,
+            last_tick: Instant::now(),
+            status: TextContent::new(""),
+        }
+    }
+
+    /// Moves playback on by the time since the last tick, making any moves
+    /// that fall due.
+    fn tick(&mut self) {
+        let now = Instant::now();
+        if !self.paused {
+            self.position += (now - self.last_tick).mul_f64(SPEEDS[self.speed]);
+        }
+        self.last_tick = now;
+
+        while self
+            .replay
+            .moves
+            .get(self.next)
+            .is_some_and(|m| m.at <= self.position)
+        {
+            self.make_next_move();
+        }
+        self.update_status();
+    }
+
+    /// Pauses and makes just the next move.
+    fn step(&mut self) {
+        self.paused = true;
+        if let Some(m) = self.replay.moves.get(self.next) {
+            self.position = m.at;
+            self.make_next_move();
+        }
+        self.update_status();
+    }
+
+    fn restart(&mut self) {
+        self.grid.game = self.replay.start();
+        self.next = 0;
+        self.position = Duration::ZERO;
+        self.update_status();
+    }
+
+    fn make_next_move(&mut self) {
+        let m = self.replay.moves[self.next];
+        self.grid.game.apply(m.action, m.index);
+        self.grid.cursor = m.index;
+        self.next += 1;
+    }
+
+    fn update_status(&self) {
+        let state = if self.next == self.replay.moves.len() {
+            "finished"
+        } else if self.paused {
+            "paused"
+        } else {
+            ""
+        };
+        self.status.set_content(format!(
+            "Replay {:>6.1}s / {:.1}s  {}x  {state}",
+            self.position.min(self.replay.duration()).as_secs_f64(),
+            self.replay.duration().as_secs_f64(),
+            SPEEDS[self.speed]
+        ));
+    }
+}
+
+impl View for ReplayView {
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, cursive::view::CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn on_event(&mut self, e: Event) -> EventResult {
+        match e {
+            Event::Char(' ') => {
+                self.paused = !self.paused;
+                self.last_tick = Instant::now();
+            }
+            Event::Char('n') | Event::Key(Key::Right) => self.step(),
+            Event::Char('+') | Event::Key(Key::Up) => {
+                self.speed = (self.speed + 1).min(SPEEDS.len() - 1)
+            }
+            Event::Char('-') | Event::Key(Key::Down) => self.speed = self.speed.saturating_sub(1),
+            Event::Char('r') => self.restart(),
+            _ => return EventResult::Ignored,
+        }
+        self.update_status();
+        EventResult::Consumed(None)
+    }
+
+    fn draw(&self, printer: &cursive::Printer) {
+        self.grid.draw(printer);
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        self.grid.required_size(constraint)
+    }
+}
+
+/// Where the cells of a board go on screen.
+#[derive(Debug, Clone, Copy)]
+struct Geometry {
+    rows: usize,
+    cols: usize,
+    /// Columns per cell, see [`Glyphs::width`].
+    width: usize,
+    hex: bool,
+}
+
+impl Geometry {
+    fn new(config: &Config, width: usize) -> Geometry {
+        Geometry {
+            rows: config.size.0,
+            cols: config.size.1,
+            width,
+            hex: config.shape == Shape::Hex,
+        }
+    }
+
+    /// Columns from one cell to the next. Hexagons take an even number, so
+    /// that odd rows can sit exactly half a cell over.
+    fn pitch(&self) -> usize {
+        match self.hex {
+            true => self.width + self.width % 2,
+            false => self.width,
+        }
+    }
+
+    /// How far `row` is shifted to the right.
+    fn stagger(&self, row: usize) -> usize {
+        match self.hex && row % 2 == 1 {
+            true => self.pitch() / 2,
+            false => 0,
+        }
+    }
+
+    /// The columns and rows the cells cover.
+    fn size(&self) -> Vec2 {
+        let widest = (0..self.rows.min(2)).map(|r| self.stagger(r)).max();
+        let x = self.cols.saturating_sub(1) * self.pitch() + self.width + widest.unwrap_or(0);
+        Vec2::new(x, self.rows)
+    }
+
+    /// Where the cell at `index` is drawn.
+    fn position(&self, (r, c): (usize, usize)) -> Vec2 {
+        Vec2::new(c * self.pitch() + self.stagger(r), r)
+    }
+
+    /// The cell at `position`, counting the gap after a cell as part of it.
+    fn cell_at(&self, position: Vec2) -> Option<(usize, usize)> {
+        let r = position.y;
+        let c = position.x.checked_sub(self.stagger(r))? / self.pitch();
+        (r < self.rows && c < self.cols).then_some((r, c))
+    }
+}
+
+/// Rows and columns taken up around the board by the game dialog.
+const BOARD_MARGIN: Vec2 = XY { x: 6, y: 9 };
+
+/// Checks that the board fits on a `screen` of the given size, with cells
+/// `width` columns wide.
+fn check_fits(config: &Config, width: usize, screen: Vec2) -> Result<(), String> {
+    let (r, c) = config.size;
+    let margin = match config.torus {
+        true => BOARD_MARGIN + (2, 2),
+        false => BOARD_MARGIN,
+    };
+    let geometry = Geometry::new(config, width);
+    let needed = geometry.size() + margin;
+    if !needed.fits_in(screen) {
+        let max = screen.saturating_sub(margin);
+        let pitch = geometry.pitch();
+        let spare = geometry.size().x - geometry.cols.saturating_sub(1) * pitch;
+        return Err(format!(
+            "A {r}x{c} board doesn't fit in this terminal (at most {}x{}).",
+            max.y,
+            (max.x + pitch).saturating_sub(spare) / pitch
+        ));
+    }
+    Ok(())
+}
+
+/// The cell width from the settings.
+fn cell_width(s: &mut Cursive) -> usize {
+    s.user_data::<Options>()
+        .map_or(3, |o| o.settings.cell_width)
+}
+
+fn new_game(s: &mut Cursive, d: &Difficulty) {
+    let setup = GameSetup {
+        config: Config::from(d),
+        difficulty: Some(*d),
+        seed: s.user_data::<Options>().and_then(|o| o.seed),
+        practice: false,
+    };
+    let setup = with_menu_options(s, setup);
+    // Not every preset can be played with every option, e.g. Beginner has
+    // too many rows to wrap hexagons.
+    if let Err(e) = setup.config.validate() {
+        s.add_layer(Dialog::info(e.to_string()));
+        return;
+    }
+    start_game(s, setup);
+}
+
+/// Applies the options picked in the start menu, and the first click rule
+/// from the settings.
+fn with_menu_options(s: &mut Cursive, setup: GameSetup) -> GameSetup {
+    let mut setup = setup;
+    if let Some(options) = s.user_data::<Options>() {
+        setup.config.safe_start = options.settings.safe_start;
+    }
+    if let Some(no_guess) = s.call_on_name("no_guess", |v: &mut Checkbox| v.is_checked()) {
+        setup.config.no_guess = no_guess;
+    }
+    if let Some(stacked) = s.call_on_name("stacked", |v: &mut Checkbox| v.is_checked()) {
+        setup.config.max_mines = if stacked { MAX_MINES_PER_CELL } else { 1 };
+    }
+    if let Some(hex) = s.call_on_name("hex", |v: &mut Checkbox| v.is_checked()) {
+        setup.config.shape = if hex { Shape::Hex } else { Shape::Square };
+    }
+    if let Some(torus) = s.call_on_name("torus", |v: &mut Checkbox| v.is_checked()) {
+        setup.config.torus = torus;
+    }
+    if let Some(practice) = s.call_on_name("practice", |v: &mut Checkbox| v.is_checked()) {
+        setup.practice = practice;
+    }
+    setup
+}
+
+/// Asks which replay file to watch, offering the last game played, on top of
+/// the start menu.
+fn watch_replay(s: &mut Cursive) {
+    fn submit(s: &mut Cursive, text: &str) {
+        let replay = replay::load(Path::new(text.trim()))
+            .map_err(|e| format!("Could not load the replay:\n{e}"))
+            .and_then(|replay| {
+                check_fits(&replay.code.config, cell_width(s), s.screen_size())?;
+                Ok(replay)
+            });
+
+        match replay {
+            Ok(replay) => {
+                s.pop_layer();
+                show_replay(s, replay);
+            }
+            Err(e) => s.add_layer(Dialog::info(e)),
+        }
+    }
+
+    let last = replay::path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_default();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("Replay file:"))
+                .child(
+                    EditView::new()
+                        .content(last)
+                        .on_submit(submit)
+                        .with_name("replay_path")
+                        .fixed_width(50),
+                ),
+        )
+        .title("Watch Replay")
+        .button("Watch", |s| {
+            let text = s
+                .call_on_name("replay_path", |v: &mut EditView| v.get_content())
+                .unwrap_or_default();
+            submit(s, &text);
+        })
+        .dismiss_button("Back"),
+    );
+}
+
+fn show_replay(s: &mut Cursive, replay: Replay) {
+    s.pop_layer();
+
+    let mut view = ReplayView::new(replay);
+    if let Some(options) = s.user_data::<Options>() {
+        view.grid.theme = options.theme.clone();
+        view.grid.glyphs = Glyphs::new(options.settings.cell_width);
+    }
+    view.update_status();
+    let status = TextView::new_with_content(view.status.clone());
+    let code = TextView::new(format!("Game code: {}", view.replay.code));
+
+    s.set_fps(10);
+    s.set_global_callback(Event::Refresh, |s| {
+        s.call_on_name("replay", |view: &mut ReplayView| view.tick());
+    });
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(status)
+                .child(Panel::new(view.with_name("replay")))
+                .child(code)
+                .child(TextView::new(
+                    "Space: pause  n: step  +/-: speed  r: restart",
+                )),
+        )
+        .title("Replay")
+        .button("Menu", start_menu),
+    );
+}
+
+/// Asks for a game code, on top of the start menu, and starts its board.
+fn load_code(s: &mut Cursive) {
+    fn submit(s: &mut Cursive, text: &str) {
+        let code = text
+            .parse::<GameCode>()
+            .map_err(|e| e.to_string())
+            .and_then(|code| {
+                code.config.validate().map_err(|e| e.to_string())?;
+                check_fits(&code.config, cell_width(s), s.screen_size())?;
+                Ok(code)
+            });
+
+        match code {
+            Ok(code) => {
+                s.pop_layer();
+                start_game(
+                    s,
+                    GameSetup {
+                        config: code.config,
+                        difficulty: None,
+                        seed: Some(code.seed),
+                        practice: false,
+                    },
+                );
+            }
+            Err(e) => s.add_layer(Dialog::info(e)),
+        }
+    }
+
+    s.add_layer(
+        Dialog::around(
+            EditView::new()
+                .on_submit(submit)
+                .with_name("game_code")
+                .fixed_width(28),
+        )
+        .title("Load Game Code")
+        .button("Start", |s| {
+            let text = s
+                .call_on_name("game_code", |v: &mut EditView| v.get_content())
+                .unwrap_or_default();
+            submit(s, &text);
+        })
+        .dismiss_button("Back"),
+    );
+}
+
+/// Asks for the size, layers and mine count of a custom board, on top of the
+/// start menu.
+fn custom_game(s: &mut Cursive) {
+    fn field(label: &str, name: &str, value: usize) -> LinearLayout {
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_width(9))
+            .child(
+                EditView::new()
+                    .content(value.to_string())
+                    .with_name(name)
+                    .fixed_width(6),
+            )
+    }
+
+    fn read(s: &mut Cursive, name: &str, label: &str) -> Result<usize, String> {
+        s.call_on_name(name, |v: &mut EditView| v.get_content())
+            .and_then(|text| text.trim().parse().ok())
+            .ok_or_else(|| format!("{label} must be a whole number."))
+    }
+
+    let default = Config::from(&Difficulty::Beginner);
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(field("Rows:", "custom_rows", default.size.0))
+                .child(field("Columns:", "custom_cols", default.size.1))
+                .child(field("Layers:", "custom_layers", default.layers))
+                .child(field("Mines:", "custom_mines", default.num_bombs as usize)),
+        )
+        .title("Custom Game")
+        .button("Start", |s| {
+            let setup = read(s, "custom_rows", "Rows").and_then(|r| {
+                let c = read(s, "custom_cols", "Columns")?;
+                let layers = read(s, "custom_layers", "Layers")?;
+                let num_bombs = read(s, "custom_mines", "Mines")?;
+                let num_bombs = u32::try_from(num_bombs).map_err(|e| e.to_string())?;
+                let config = Config {
+                    layers,
+                    ..Config::custom((r, c), num_bombs)
+                };
+                let setup = GameSetup {
+                    config,
+                    difficulty: None,
+                    seed: s.user_data::<Options>().and_then(|o| o.seed),
+                    practice: false,
+                };
+                // The menu options decide how many mines fit and whether the
+                // board can wrap, so they go on before checking.
+                let setup = with_menu_options(s, setup);
+                setup.config.validate().map_err(|e| e.to_string())?;
+                check_fits(&setup.config, cell_width(s), s.screen_size())?;
+                Ok(setup)
+            });
+
+            match setup {
+                Ok(setup) => {
+                    s.pop_layer();
+                    start_game(s, setup);
+                }
+                Err(e) => s.add_layer(Dialog::info(e)),
+            }
+        })
+        .dismiss_button("Back"),
+    );
+}
+
+/// Replaces the current layer with a fresh board.
+fn start_game(s: &mut Cursive, setup: GameSetup) {
+    show_game(s, Grid::new(setup));
+}
+
+/// Replaces the start menu with the game saved by "Save & Quit". The save is
+/// deleted once it has been loaded.
+fn resume_game(s: &mut Cursive) {
+    let saved = match save::load() {
+        Ok(Some(saved)) => saved,
+        Ok(None) => {
+            s.add_layer(Dialog::info("There is no saved game."));
+            return;
+        }
+        Err(e) => {
+            s.add_layer(
+                Dialog::text(format!("Could not resume the saved game:\n{e}"))
+                    .button("Delete It", |s| {
+                        let _ = save::discard();
+                        s.pop_layer();
+                        start_menu(s);
+                    })
+                    .dismiss_button("Back"),
+            );
+            return;
+        }
+    };
+    if let Err(e) = save::discard() {
+        s.add_layer(Dialog::info(format!(
+            "Could not delete the saved game:\n{e}"
+        )));
+        return;
+    }
+
+    let setup = GameSetup {
+        config: saved.game.config().clone(),
+        difficulty: saved.difficulty,
+        seed: saved.fixed_seed.then(|| saved.game.seed()),
+        practice: saved.game.is_practice(),
+    };
+    show_game(s, Grid::with_game(setup, saved.game));
+}
+
+/// Saves the game and quits. A finished game has nothing worth saving, so
+/// it just quits.
+fn save_and_quit(s: &mut Cursive) {
+    let Some(saved) = s.call_on_name("grid", |grid: &mut Grid| grid.saved()) else {
+        return;
+    };
+    if saved.game.is_over() {
+        s.quit();
+        return;
+    }
+    match save::save(&saved) {
+        Ok(()) => s.quit(),
+        Err(e) => s.add_layer(Dialog::info(format!("Could not save the game:\n{e}"))),
+    }
+}
+
+/// The game code to share the board, and the seed on its own to pass to
+/// `--seed`.
+fn game_code(game: &Game) -> String {
+    format!("Game code: {}\nSeed: {}", game.code(), game.seed())
+}
+
+fn show_game(s: &mut Cursive, grid: Grid) {
+    s.pop_layer();
+
+    let mut grid = grid;
+    if let Some(options) = s.user_data::<Options>() {
+        grid.game
+            .set_question_marks(options.settings.question_marks);
+        grid.theme = options.theme.clone();
+        grid.glyphs = Glyphs::new(options.settings.cell_width);
+        grid.bindings = options.settings.bindings;
+    }
+
+    grid.update_status();
+    let status = TextView::new_with_content(grid.status.clone());
+    let code = TextView::new(game_code(&grid.game));
+
+    s.set_fps(4);
+    s.set_global_callback(Event::Refresh, |s| {
+        s.call_on_name("grid", |grid: &mut Grid| grid.update_status());
+    });
+
+    // The loss dialog goes in the row beside the board.
+    s.add_layer(
+        LinearLayout::horizontal()
+            .child(Dialog::around(
+                LinearLayout::vertical()
+                    .child(status)
+                    .child(Panel::new(grid.with_name("grid")))
+                    .child(code)
+                    .child(
+                        LinearLayout::horizontal()
+                            .child(Button::new("Save & Quit", save_and_quit))
+                            .child(DummyView.fixed_width(2))
+                            .child(Button::new("Quit", |s| s.quit())),
+                    ),
+            ))
+            .with_name("game"),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use mines::Board;
+
+    use super::*;
+
+    fn setup(config: Config) -> GameSetup {
+        GameSetup {
+            config,
+            difficulty: None,
+            seed: None,
+            practice: false,
+        }
+    }
+
+    /// A grid picking up a game on `board` as it stands.
+    fn resumed(config: Config, board: Board) -> Grid {
+        let game = Game::resume(
+            config.clone(),
+            0,
+            board,
+            Duration::ZERO,
+            0,
+            Vec::new(),
+            None,
+        );
+        Grid::with_game(setup(config), game)
+    }
+
+    #[test]
+    fn test_cursor_stays_on_board() {
+        let mut grid = Grid::new(setup(Config::custom((3, 4), 1)));
+        grid.on_event(Event::Key(Key::Up));
+        grid.on_event(Event::Char('a'));
+        assert_eq!(grid.cursor, (0, 0).into());
+
+        for _ in 0..5 {
+            grid.on_event(Event::Char('j'));
+            grid.on_event(Event::Key(Key::Right));
+        }
+        assert_eq!(grid.cursor, (2, 3).into());
+
+        grid.on_event(Event::Char('f'));
+        assert_eq!(grid.game.board()[(2, 3)].state, CellState::Flagged(1));
+        assert!(grid.status.get_content().source().starts_with("Mines: 0"));
+    }
+
+    #[test]
+    fn test_release_off_board() {
+        let mut grid = Grid::new(GameSetup {
+            seed: Some(1),
+            ..setup(Config::custom((3, 4), 6))
+        });
+        let click = |x, y, event| Event::Mouse {
+            offset: Vec2::zero(),
+            position: Vec2::new(x, y),
+            event,
+        };
+        grid.on_event(click(0, 0, MouseEvent::Press(MouseButton::Left)));
+        grid.on_event(click(40, 40, MouseEvent::Release(MouseButton::Left)));
+        assert!(!grid.left_down);
+
+        // A right click on its own flags rather than chords.
+        let hidden = grid
+            .game
+            .board()
+            .indices()
+            .find(|&i| grid.game.board()[i].state == CellState::Hidden)
+            .unwrap();
+        grid.on_event(click(
+            hidden.col * 3,
+            hidden.row,
+            MouseEvent::Press(MouseButton::Right),
+        ));
+        assert_eq!(grid.game.board()[hidden].state, CellState::Flagged(1));
+    }
+
+    #[test]
+    fn test_practice_undo() {
+        let mut grid = Grid::new(GameSetup {
+            seed: Some(1),
+            practice: true,
+            ..setup(Config::custom((3, 3), 1))
+        });
+        grid.on_event(Event::Char('f'));
+        assert_eq!(grid.game.board()[(0, 0)].state, CellState::Flagged(1));
+
+        grid.on_event(Event::Char('u'));
+        assert_eq!(grid.game.board()[(0, 0)].state, CellState::Hidden);
+        grid.on_event(Event::CtrlChar('r'));
+        assert_eq!(grid.game.board()[(0, 0)].state, CellState::Flagged(1));
+        assert_eq!(grid.game.undos(), 1);
+    }
+
+    #[test]
+    fn test_post_mortem() {
+        let config = Config::custom((3, 3), 2);
+        let mut board = Board::new(config.size);
+        board.place_bomb((0, 0));
+        board.place_bomb((2, 2));
+        let mut grid = resumed(config, board);
+
+        grid.game.toggle_flag((0, 1));
+        assert_eq!(grid.look((0, 1).into(), None).0, "[~]");
+        assert_eq!(grid.look((0, 0).into(), None).0, "[#]");
+
+        grid.game.reveal((2, 2));
+        assert_eq!(
+            grid.look((0, 1).into(), None),
+            ("[X]", grid.theme.wrong_flag())
+        );
+        assert_eq!(grid.look((0, 0).into(), None), ("[*]", grid.theme.mine()));
+        assert_eq!(
+            grid.look((2, 2).into(), None),
+            ("[*]", grid.theme.exploded())
+        );
+        assert_eq!(grid.look((1, 1).into(), None).0, "[#]");
+    }
+
+    #[test]
+    fn test_hint_with_misplaced_flag() {
+        // Two flags around the 1, so the mine under the middle isn't safe.
+        let config = Config::custom((2, 5), 2);
+        let mut board = Board::new(config.size);
+        board.place_bomb((1, 1));
+        board.place_bomb((1, 4));
+        board[(0, 1)].state = CellState::Revealed;
+        board[(0, 0)].state = CellState::Flagged(1);
+        board[(0, 2)].state = CellState::Flagged(1);
+        let mut grid = resumed(config, board);
+
+        grid.on_event(Event::Char('?'));
+        assert_eq!(grid.hint, None);
+        assert_eq!(
+            grid.message,
+            Some("Hint: the flags don't add up, one of them is wrong.")
+        );
+        assert_eq!(grid.game.hints(), 0);
+    }
+
+    #[test]
+    fn test_odds_overlay() {
+        // The 1 needs a mine next to it, but the only mine is flagged
+        // elsewhere.
+        let config = Config::custom((1, 3), 1);
+        let mut board = Board::new(config.size);
+        board.place_bomb((0, 1));
+        board[(0, 0)].state = CellState::Revealed;
+        board[(0, 2)].state = CellState::Flagged(1);
+        let mut grid = resumed(config, board);
+        grid.cursor = (0, 2).into();
+
+        grid.on_event(Event::Char('p'));
+        assert!(matches!(grid.odds, Some(Err(OddsError::Contradiction))));
+        assert!(grid
+            .status
+            .get_content()
+            .source()
+            .ends_with("(no odds: the flags don't add up)"));
+
+        grid.on_event(Event::Char('f'));
+        assert!(matches!(grid.odds, Some(Ok(_))));
+        grid.on_event(Event::Char('p'));
+        assert!(grid.odds.is_none());
+    }
+
+    #[test]
+    fn test_stacked_glyphs() {
+        let three = Glyphs::new(3);
+        assert_eq!(three.numbers.len(), 27);
+        assert_eq!(three.numbers[9], "[9]");
+        assert_eq!(three.numbers[24], "24 ");
+        assert_eq!(Glyphs::new(2).numbers[12], "12");
+        assert_eq!(Glyphs::new(1).numbers[10], "A");
+        assert_eq!(Glyphs::new(1).numbers[24], "O");
+        assert_eq!(Glyphs::new(1).numbers[26], "Q");
+        assert_eq!(three.flags, ["[~]", "[=]", "[≡]"]);
+
+        let config = Config {
+            max_mines: 3,
+            ..Config::custom((3, 3), 4)
+        };
+        let mut board = Board::with_max_mines(config.topology(), 3);
+        for _ in 0..3 {
+            board.place_bomb((0, 0));
+        }
+        board.place_bomb((2, 2));
+        let mut grid = resumed(config, board);
+
+        grid.game.toggle_flag((0, 0));
+        grid.game.toggle_flag((0, 0));
+        assert_eq!(grid.look((0, 0).into(), None).0, "[=]");
+        grid.game.reveal((1, 1));
+        assert_eq!(
+            grid.look((1, 1).into(), None),
+            ("[4]", grid.theme.number(4))
+        );
+
+        // Two flags on three mines is wrong once the game is lost.
+        grid.game.reveal((2, 2));
+        assert_eq!(grid.look((0, 0).into(), None).0, "[X]");
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(percent(0.0, 3), " 0%");
+        assert_eq!(percent(0.001, 3), " 1%");
+        assert_eq!(percent(0.25, 3), "25%");
+        assert_eq!(percent(0.999, 3), "99%");
+        assert_eq!(percent(1.0 - 1e-12, 3), "100");
+
+        assert_eq!(percent(0.25, 2), "25");
+        assert_eq!(percent(1.0, 2), "!!");
+        assert_eq!(percent(0.0, 1), "0");
+        assert_eq!(percent(0.01, 1), "1");
+        assert_eq!(percent(0.99, 1), "9");
+    }
+
+    #[test]
+    fn test_game_code() {
+        let game = Game::with_seed(Config::from(&Difficulty::Beginner), 123456789);
+        assert_eq!(
+            game_code(&game),
+            format!("Game code: {}\nSeed: 123456789", game.code())
+        );
+    }
+
+    #[test]
+    fn test_check_fits() {
+        let screen = Vec2::new(80, 25);
+        assert!(check_fits(&Config::custom((9, 9), 10), 3, screen).is_ok());
+        assert!(check_fits(&Config::custom((16, 24), 80), 3, screen).is_ok());
+        assert!(check_fits(&Config::custom((17, 24), 80), 3, screen).is_err());
+        assert!(check_fits(&Config::custom((16, 30), 99), 3, screen).is_err());
+        assert!(check_fits(&Config::custom((16, 30), 99), 2, screen).is_ok());
+
+        let mut torus = Config::custom((16, 24), 80);
+        torus.torus = true;
+        assert!(check_fits(&torus, 3, screen).is_err());
+        torus.size = (14, 23);
+        assert!(check_fits(&torus, 3, screen).is_ok());
+
+        let mut hex = Config::custom((16, 18), 40);
+        hex.shape = Shape::Hex;
+        assert!(check_fits(&hex, 3, screen).is_ok());
+        hex.size = (16, 19);
+        assert_eq!(
+            check_fits(&hex, 3, screen),
+            Err("A 16x19 board doesn't fit in this terminal (at most 16x18).".to_string())
+        );
+    }
+
+    #[test]
+    fn test_torus_grid() {
+        let mut config = Config::custom((3, 4), 1);
+        config.torus = true;
+        let mut grid = Grid::new(GameSetup {
+            difficulty: Some(Difficulty::Beginner),
+            ..setup(config)
+        });
+        assert_eq!(grid.required_size(Vec2::zero()), Vec2::new(14, 5));
+
+        grid.on_event(Event::Key(Key::Up));
+        grid.on_event(Event::Key(Key::Left));
+        assert_eq!(grid.cursor, (2, 3).into());
+
+        // Clicks land one row and column in, past the wrap indicators.
+        grid.on_event(Event::Mouse {
+            offset: Vec2::zero(),
+            position: Vec2::new(4, 2),
+            event: MouseEvent::Press(MouseButton::Right),
+        });
+        assert_eq!(grid.game.board()[(1, 1)].state, CellState::Flagged(1));
+
+        let category = grid.setup.ranked().unwrap();
+        assert_eq!(category.name(), "Beginner (torus)");
+        assert_eq!(Category::all().count(), 12);
+    }
+
+    #[test]
+    fn test_layered_grid() {
+        let mut config = Config::custom((3, 4), 1);
+        config.layers = 3;
+        let mut grid = Grid::new(GameSetup {
+            difficulty: Some(Difficulty::Beginner),
+            ..setup(config)
+        });
+        assert!(grid.setup.ranked().is_none());
+        // One layer at a time.
+        assert_eq!(grid.required_size(Vec2::zero()), Vec2::new(12, 3));
+
+        grid.on_event(Event::Key(Key::Down));
+        grid.on_event(Event::Key(Key::Right));
+        grid.on_event(Event::Key(Key::PageDown));
+        assert_eq!(grid.cursor, Pos::new(1, 1, 1));
+        grid.on_event(Event::Key(Key::PageDown));
+        grid.on_event(Event::Key(Key::PageDown));
+        assert_eq!(grid.cursor, Pos::new(2, 1, 1));
+        // The cursor stays on its layer.
+        grid.on_event(Event::Key(Key::Down));
+        grid.on_event(Event::Key(Key::Down));
+        assert_eq!(grid.cursor, Pos::new(2, 2, 1));
+        grid.on_event(Event::Key(Key::PageUp));
+        assert_eq!(grid.cursor, Pos::new(1, 2, 1));
+
+        // Clicks land on the layer on screen.
+        grid.on_event(Event::Mouse {
+            offset: Vec2::zero(),
+            position: Vec2::new(9, 0),
+            event: MouseEvent::Press(MouseButton::Right),
+        });
+        let flagged = Pos::new(1, 0, 3);
+        assert_eq!(grid.game.board()[flagged].state, CellState::Flagged(1));
+        assert_eq!(grid.cursor, flagged);
+    }
+
+    #[test]
+    fn test_hex_geometry() {
+        let mut config = Config::custom((3, 4), 1);
+        config.shape = Shape::Hex;
+
+        // [1] [2] [3] [4]
+        //   [1] [2] [3] [4]
+        let g = Geometry::new(&config, 3);
+        assert_eq!(g.size(), Vec2::new(17, 3));
+        assert_eq!(g.position((1, 0)), Vec2::new(2, 1));
+        assert_eq!(g.position((2, 3)), Vec2::new(12, 2));
+        assert_eq!(g.cell_at(Vec2::new(3, 0)), Some((0, 0)));
+        assert_eq!(g.cell_at(Vec2::new(4, 0)), Some((0, 1)));
+        assert_eq!(g.cell_at(Vec2::new(1, 1)), None);
+        assert_eq!(g.cell_at(Vec2::new(2, 1)), Some((1, 0)));
+        assert_eq!(g.cell_at(Vec2::new(16, 1)), Some((1, 3)));
+        assert_eq!(g.cell_at(Vec2::new(18, 1)), None);
+
+        // 1 2 3 4
+        //  1 2 3 4
+        for width in [1, 2] {
+            let g = Geometry::new(&config, width);
+            assert_eq!(g.size(), Vec2::new(7 + width, 3), "{width}");
+            assert_eq!(g.cell_at(Vec2::new(3, 1)), Some((1, 1)), "{width}");
+        }
+
+        let mut grid = Grid::new(setup(config.clone()));
+        grid.on_event(Event::Mouse {
+            offset: Vec2::zero(),
+            position: Vec2::new(6, 1),
+            event: MouseEvent::Press(MouseButton::Right),
+        });
+        assert_eq!(grid.game.board()[(1, 1)].state, CellState::Flagged(1));
+
+        // A single row has nothing to stagger.
+        config.size = (1, 4);
+        assert_eq!(Geometry::new(&config, 3).size(), Vec2::new(15, 1));
+
+        let category = Category {
+            difficulty: Difficulty::Expert,
+            shape: Shape::Hex,
+            torus: true,
+        };
+        assert_eq!(category.name(), "Expert (hex torus)");
+    }
+}